@@ -0,0 +1,530 @@
+//! Typed application configuration.
+//!
+//! Settings are read from a TOML file (`CONFIG_FILE`, default `config.toml`; a missing default
+//! file is fine) and then overridden by environment variables, so existing `.env` setups keep
+//! working. Everything is validated before the server starts and all problems are reported
+//! together.
+
+use crate::db::config::{CeremonyStore, DbBackend, DbConfig};
+use log::LevelFilter;
+use serde::Deserialize;
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use webauthn_rs::prelude::Url;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+/// Development-only JWT secret, refused unless `jwt.dev_mode` is set.
+pub const DEFAULT_JWT_SECRET: &str = "notsosecuresecret";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("cannot read config file {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid config file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("invalid value {value:?} for {var}: {reason}")]
+    Env {
+        var: &'static str,
+        value: String,
+        reason: String,
+    },
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+    #[error("cannot load TLS certificate or key: {0}")]
+    Tls(String),
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub webauthn: WebauthnConfig,
+    pub database: DbConfig,
+    pub jwt: JwtConfig,
+    pub cors: CorsConfig,
+    pub ceremonies: CeremonyConfig,
+    pub accounts: AccountConfig,
+    pub polls: PollConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    /// Default log filter; `RUST_LOG` still wins when set
+    pub log_level: String,
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "0.0.0.0".to_string(),
+            port: 3000,
+            log_level: "info".to_string(),
+            tls: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebauthnConfig {
+    pub rp_id: String,
+    /// Shown by authenticators; defaults to `rp_id`
+    pub rp_name: Option<String>,
+    /// Origins passkey ceremonies may come from; each must be on `rp_id` or a subdomain of it
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for WebauthnConfig {
+    fn default() -> Self {
+        WebauthnConfig {
+            rp_id: "localhost".to_string(),
+            rp_name: None,
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Directory of Ed25519 `<kid>.pub.pem`/`<kid>.key.pem` pairs; HS256 with `secret` without it
+    pub keys_dir: Option<PathBuf>,
+    pub signing_kid: Option<String>,
+    pub secret: Option<String>,
+    /// Allows the built-in development secret
+    pub dev_mode: bool,
+    pub access_token_minutes: i64,
+    pub refresh_token_days: i64,
+    pub recovery_token_minutes: i64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        JwtConfig {
+            keys_dir: None,
+            signing_kid: None,
+            secret: None,
+            dev_mode: false,
+            access_token_minutes: 15,
+            refresh_token_days: 30,
+            recovery_token_minutes: 10,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser
+    pub allowed_origins: Vec<String>,
+    /// Origins allowed to read public endpoints such as poll results; empty allows any origin
+    pub public_origins: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            public_origins: Vec::new(),
+            allow_credentials: true,
+            max_age_secs: Some(3600),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CeremonyConfig {
+    /// Seconds a pending passkey ceremony stays valid
+    pub ttl_secs: u64,
+    /// Seconds between sweeps of expired ceremonies
+    pub sweep_secs: u64,
+    /// Defaults to the database backend
+    pub store: Option<CeremonyStore>,
+}
+
+impl Default for CeremonyConfig {
+    fn default() -> Self {
+        CeremonyConfig {
+            ttl_secs: 300,
+            sweep_secs: 60,
+            store: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountConfig {
+    /// Usernames made admins when they register
+    pub admin_users: Vec<String>,
+    /// Hours trustees have to approve a social recovery
+    pub social_recovery_hours: i64,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        AccountConfig {
+            admin_users: vec![],
+            social_recovery_hours: 72,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollConfig {
+    /// Seconds between sweeps that open scheduled polls and expire those past their deadline
+    pub status_sweep_secs: u64,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        PollConfig {
+            status_sweep_secs: 30,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Reads the config file, applies environment overrides and validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => AppConfig::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        override_parsed("BIND_ADDRESS", &mut self.server.bind_address)?;
+        override_parsed("PORT", &mut self.server.port)?;
+        override_parsed("LOG_LEVEL", &mut self.server.log_level)?;
+        match (env::var("TLS_CERT_PATH"), env::var("TLS_KEY_PATH")) {
+            (Ok(cert_path), Ok(key_path)) => {
+                self.server.tls = Some(TlsConfig {
+                    cert_path: cert_path.into(),
+                    key_path: key_path.into(),
+                })
+            }
+            (Err(_), Err(_)) => {}
+            (Ok(value), Err(_)) | (Err(_), Ok(value)) => {
+                return Err(ConfigError::Env {
+                    var: "TLS_CERT_PATH/TLS_KEY_PATH",
+                    value,
+                    reason: "both must be set together".to_string(),
+                })
+            }
+        }
+
+        override_parsed("RP_ID", &mut self.webauthn.rp_id)?;
+        override_optional("RP_NAME", &mut self.webauthn.rp_name)?;
+        override_list("RP_ORIGINS", &mut self.webauthn.allowed_origins);
+
+        override_parsed::<DbBackend>("DB_BACKEND", &mut self.database.backend)?;
+        override_parsed("DATABASE_URI", &mut self.database.connection_string)?;
+        override_parsed("DATABASE_NAME", &mut self.database.database_name)?;
+
+        override_optional("JWT_KEYS_DIR", &mut self.jwt.keys_dir)?;
+        override_optional("JWT_SIGNING_KID", &mut self.jwt.signing_kid)?;
+        override_optional("SECRET", &mut self.jwt.secret)?;
+        override_parsed("JWT_DEV_MODE", &mut self.jwt.dev_mode)?;
+        override_parsed("ACCESS_TOKEN_MINUTES", &mut self.jwt.access_token_minutes)?;
+        override_parsed("REFRESH_TOKEN_DAYS", &mut self.jwt.refresh_token_days)?;
+        override_parsed(
+            "RECOVERY_TOKEN_MINUTES",
+            &mut self.jwt.recovery_token_minutes,
+        )?;
+
+        override_list("CORS_ORIGINS", &mut self.cors.allowed_origins);
+        override_list("CORS_PUBLIC_ORIGINS", &mut self.cors.public_origins);
+        override_parsed("CORS_ALLOW_CREDENTIALS", &mut self.cors.allow_credentials)?;
+        override_optional("CORS_MAX_AGE_SECS", &mut self.cors.max_age_secs)?;
+
+        override_parsed("CEREMONY_TTL_SECS", &mut self.ceremonies.ttl_secs)?;
+        override_parsed("CEREMONY_SWEEP_SECS", &mut self.ceremonies.sweep_secs)?;
+        override_optional::<CeremonyStore>("CEREMONY_STORE", &mut self.ceremonies.store)?;
+
+        override_list("ADMIN_USERS", &mut self.accounts.admin_users);
+        override_parsed(
+            "SOCIAL_RECOVERY_HOURS",
+            &mut self.accounts.social_recovery_hours,
+        )?;
+
+        override_parsed("POLL_STATUS_SWEEP_SECS", &mut self.polls.status_sweep_secs)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.server.bind_address.parse::<IpAddr>().is_err() {
+            problems.push(format!(
+                "server.bind_address {:?} is not an IP address",
+                self.server.bind_address
+            ));
+        }
+        if LevelFilter::from_str(&self.server.log_level).is_err() {
+            problems.push(format!(
+                "server.log_level {:?} must be one of off, error, warn, info, debug, trace",
+                self.server.log_level
+            ));
+        }
+        if let Some(tls) = &self.server.tls {
+            for (name, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+                if !path.is_file() {
+                    problems.push(format!(
+                        "server.tls.{} {} does not exist",
+                        name,
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        if self.webauthn.rp_id.is_empty() {
+            problems.push("webauthn.rp_id must not be empty".to_string());
+        }
+        if self.webauthn.allowed_origins.is_empty() {
+            problems.push("webauthn.allowed_origins must list at least one origin".to_string());
+        }
+        for origin in &self.webauthn.allowed_origins {
+            match parse_origin(origin) {
+                Ok(url) => {
+                    let on_rp_id = url.domain().is_some_and(|domain| {
+                        domain == self.webauthn.rp_id
+                            || domain.ends_with(&format!(".{}", self.webauthn.rp_id))
+                    });
+                    if !on_rp_id {
+                        problems.push(format!(
+                            "webauthn.allowed_origins: {} is not on rp_id {:?} or a subdomain of it",
+                            origin, self.webauthn.rp_id
+                        ));
+                    }
+                }
+                Err(e) => problems.push(format!("webauthn.allowed_origins: {}", e)),
+            }
+        }
+
+        if self.database.connection_string.is_empty() {
+            problems.push("database.connection_string must not be empty".to_string());
+        }
+        if self.database.database_name.is_empty() {
+            problems.push("database.database_name must not be empty".to_string());
+        }
+
+        match (&self.jwt.keys_dir, &self.jwt.signing_kid) {
+            (Some(_), None) => {
+                problems.push("jwt.signing_kid is required when jwt.keys_dir is set".to_string())
+            }
+            (Some(dir), Some(_)) if !dir.is_dir() => {
+                problems.push(format!("jwt.keys_dir {} is not a directory", dir.display()))
+            }
+            (None, _) => {
+                let secret = self.jwt.secret.as_deref().unwrap_or(DEFAULT_JWT_SECRET);
+                if secret == DEFAULT_JWT_SECRET && !self.jwt.dev_mode {
+                    problems.push(
+                        "no JWT signing keys: set jwt.keys_dir and jwt.signing_kid, set \
+                         jwt.secret, or set jwt.dev_mode = true to use the default secret"
+                            .to_string(),
+                    );
+                }
+            }
+            _ => {}
+        }
+        for (name, value) in [
+            ("jwt.access_token_minutes", self.jwt.access_token_minutes),
+            ("jwt.refresh_token_days", self.jwt.refresh_token_days),
+            (
+                "jwt.recovery_token_minutes",
+                self.jwt.recovery_token_minutes,
+            ),
+            (
+                "accounts.social_recovery_hours",
+                self.accounts.social_recovery_hours,
+            ),
+        ] {
+            if value <= 0 {
+                problems.push(format!("{} must be positive", name));
+            }
+        }
+
+        for origin in &self.cors.allowed_origins {
+            if let Err(e) = parse_origin(origin) {
+                problems.push(format!("cors.allowed_origins: {}", e));
+            }
+        }
+        for origin in &self.cors.public_origins {
+            if let Err(e) = parse_origin(origin) {
+                problems.push(format!("cors.public_origins: {}", e));
+            }
+        }
+
+        if self.ceremonies.ttl_secs == 0 {
+            problems.push("ceremonies.ttl_secs must be positive".to_string());
+        }
+        if self.ceremonies.sweep_secs == 0 {
+            problems.push("ceremonies.sweep_secs must be positive".to_string());
+        }
+        if self.polls.status_sweep_secs == 0 {
+            problems.push("polls.status_sweep_secs must be positive".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// Where ceremonies live: the configured store, or the database backend.
+    pub fn ceremony_store(&self) -> CeremonyStore {
+        self.ceremonies
+            .store
+            .unwrap_or(match self.database.backend {
+                DbBackend::MongoDb => CeremonyStore::MongoDb,
+            })
+    }
+}
+
+impl TlsConfig {
+    /// Loads the PEM certificate chain and private key into a rustls server config.
+    pub fn server_config(&self) -> Result<rustls::ServerConfig, ConfigError> {
+        let tls_error =
+            |path: &Path, e: &dyn Display| ConfigError::Tls(format!("{}: {}", path.display(), e));
+
+        let cert_file = File::open(&self.cert_path).map_err(|e| tls_error(&self.cert_path, &e))?;
+        let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+            .map_err(|e| tls_error(&self.cert_path, &e))?
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        if certs.is_empty() {
+            return Err(tls_error(&self.cert_path, &"no certificates found"));
+        }
+
+        let key_file = File::open(&self.key_path).map_err(|e| tls_error(&self.key_path, &e))?;
+        let key = rustls_pemfile::read_all(&mut BufReader::new(key_file))
+            .map_err(|e| tls_error(&self.key_path, &e))?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+                _ => None,
+            })
+            .ok_or_else(|| tls_error(&self.key_path, &"no private key found"))?;
+
+        rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| ConfigError::Tls(e.to_string()))
+    }
+}
+
+/// Parses a bare origin such as `https://example.com:8443`, without path or trailing slash,
+/// which is the form browsers send in the `Origin` header.
+fn parse_origin(origin: &str) -> Result<Url, String> {
+    let url = Url::parse(origin).map_err(|e| format!("{:?} is not a URL ({})", origin, e))?;
+    if url.origin().ascii_serialization() != origin {
+        return Err(format!(
+            "{:?} must be a bare origin such as https://example.com",
+            origin
+        ));
+    }
+    Ok(url)
+}
+
+fn parse_env<T>(var: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(var) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e: T::Err| ConfigError::Env {
+                var,
+                value,
+                reason: e.to_string(),
+            }),
+        Err(_) => Ok(None),
+    }
+}
+
+fn override_parsed<T>(var: &'static str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = parse_env(var)? {
+        *target = value;
+    }
+    Ok(())
+}
+
+fn override_optional<T>(var: &'static str, target: &mut Option<T>) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = parse_env(var)? {
+        *target = Some(value);
+    }
+    Ok(())
+}
+
+/// Comma separated list, blank entries dropped.
+fn override_list(var: &'static str, target: &mut Vec<String>) {
+    if let Ok(value) = env::var(var) {
+        *target = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+}
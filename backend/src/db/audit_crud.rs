@@ -0,0 +1,9 @@
+use crate::models::audit::AuditEvent;
+
+#[async_trait::async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn record(
+        &self,
+        event: AuditEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
@@ -0,0 +1,34 @@
+use crate::models::ceremony::{AuthenticationCeremony, DiscoverableCeremony, RegistrationCeremony};
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait CeremonyStateRepository: Send + Sync {
+    /// Stores the ceremony under a fresh ceremony ID and returns that ID.
+    async fn insert_registration(
+        &self,
+        ceremony: RegistrationCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>>;
+    /// Removes and returns the ceremony, unless it is unknown or has expired.
+    async fn take_registration(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<RegistrationCeremony>, Box<dyn std::error::Error + Send + Sync>>;
+    async fn insert_authentication(
+        &self,
+        ceremony: AuthenticationCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>>;
+    async fn take_authentication(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<AuthenticationCeremony>, Box<dyn std::error::Error + Send + Sync>>;
+    async fn insert_discoverable(
+        &self,
+        ceremony: DiscoverableCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>>;
+    async fn take_discoverable(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<DiscoverableCeremony>, Box<dyn std::error::Error + Send + Sync>>;
+    /// Drops every expired ceremony and returns how many were removed.
+    async fn sweep_expired(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>;
+}
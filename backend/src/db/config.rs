@@ -1,19 +1,72 @@
-#[derive(Clone)]
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Storage backend for the repositories.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    MongoDb,
+}
+
+impl FromStr for DbBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mongodb" => Ok(DbBackend::MongoDb),
+            _ => Err("expected \"mongodb\"".to_string()),
+        }
+    }
+}
+
+/// Where pending passkey ceremonies are kept.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CeremonyStore {
+    /// In this process only; fine for a single replica
+    Memory,
+    /// Shared between replicas
+    MongoDb,
+}
+
+impl FromStr for CeremonyStore {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(CeremonyStore::Memory),
+            "mongodb" => Ok(CeremonyStore::MongoDb),
+            _ => Err("expected \"memory\" or \"mongodb\"".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CeremonyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CeremonyStore::Memory => f.write_str("memory"),
+            CeremonyStore::MongoDb => f.write_str("mongodb"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
 pub struct DbConfig {
-    pub db_type: String,           // Type of the database
+    pub backend: DbBackend,        // Type of the database
     pub connection_string: String, // Connection string to the database
     //pub username: Option<String>, // Optional username for authentication
     //pub password: Option<String>, // Optional password for authentication
     pub database_name: String, // Name of the database to use
 }
 
-impl DbConfig {
-    // Implement the new function to initialize DbConfig
-    pub fn new(db_type: &str, connection_string: String, db_name: &str) -> Self {
+impl Default for DbConfig {
+    fn default() -> Self {
         Self {
-            db_type: db_type.to_string(),
-            connection_string: connection_string,
-            database_name: db_name.to_string(),
+            backend: DbBackend::MongoDb,
+            connection_string: "mongodb://localhost:27017/?replicaSet=rs0".to_string(),
+            database_name: "rustest".to_string(),
         }
     }
 }
@@ -0,0 +1,105 @@
+use crate::db::ceremony_crud::CeremonyStateRepository;
+use crate::models::ceremony::{AuthenticationCeremony, DiscoverableCeremony, RegistrationCeremony};
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Keeps ceremonies in process memory. Only suitable for a single backend instance.
+pub struct MemoryCeremonyRepo {
+    // Maps of ceremony IDs to the time they were started and their details
+    registrations: Mutex<HashMap<Uuid, (Instant, RegistrationCeremony)>>,
+    authentications: Mutex<HashMap<Uuid, (Instant, AuthenticationCeremony)>>,
+    discoverables: Mutex<HashMap<Uuid, (Instant, DiscoverableCeremony)>>,
+    ttl: Duration,
+}
+
+impl MemoryCeremonyRepo {
+    pub fn new(ttl: Duration) -> Self {
+        MemoryCeremonyRepo {
+            registrations: Mutex::new(HashMap::new()),
+            authentications: Mutex::new(HashMap::new()),
+            discoverables: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CeremonyStateRepository for MemoryCeremonyRepo {
+    async fn insert_registration(
+        &self,
+        ceremony: RegistrationCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        let ceremony_id = Uuid::new_v4();
+        let mut map = self.registrations.lock().await;
+        map.insert(ceremony_id, (Instant::now(), ceremony));
+        Ok(ceremony_id)
+    }
+
+    async fn take_registration(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<RegistrationCeremony>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = self.registrations.lock().await;
+        Ok(map
+            .remove(&ceremony_id)
+            .filter(|(started, _)| started.elapsed() < self.ttl)
+            .map(|(_, ceremony)| ceremony))
+    }
+
+    async fn insert_authentication(
+        &self,
+        ceremony: AuthenticationCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        let ceremony_id = Uuid::new_v4();
+        let mut map = self.authentications.lock().await;
+        map.insert(ceremony_id, (Instant::now(), ceremony));
+        Ok(ceremony_id)
+    }
+
+    async fn take_authentication(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<AuthenticationCeremony>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = self.authentications.lock().await;
+        Ok(map
+            .remove(&ceremony_id)
+            .filter(|(started, _)| started.elapsed() < self.ttl)
+            .map(|(_, ceremony)| ceremony))
+    }
+
+    async fn insert_discoverable(
+        &self,
+        ceremony: DiscoverableCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        let ceremony_id = Uuid::new_v4();
+        let mut map = self.discoverables.lock().await;
+        map.insert(ceremony_id, (Instant::now(), ceremony));
+        Ok(ceremony_id)
+    }
+
+    async fn take_discoverable(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<DiscoverableCeremony>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut map = self.discoverables.lock().await;
+        Ok(map
+            .remove(&ceremony_id)
+            .filter(|(started, _)| started.elapsed() < self.ttl)
+            .map(|(_, ceremony)| ceremony))
+    }
+
+    async fn sweep_expired(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let mut registrations = self.registrations.lock().await;
+        let mut authentications = self.authentications.lock().await;
+        let mut discoverables = self.discoverables.lock().await;
+        let before = registrations.len() + authentications.len() + discoverables.len();
+        registrations.retain(|_, (started, _)| started.elapsed() < self.ttl);
+        authentications.retain(|_, (started, _)| started.elapsed() < self.ttl);
+        discoverables.retain(|_, (started, _)| started.elapsed() < self.ttl);
+        let after = registrations.len() + authentications.len() + discoverables.len();
+        Ok((before - after) as u64)
+    }
+}
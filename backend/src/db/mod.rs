@@ -1,30 +1,75 @@
+pub mod audit_crud;
+pub mod ceremony_crud;
 pub mod config;
+pub mod memory_ceremony_crud;
+pub mod mongo_audit_crud;
+pub mod mongo_ceremony_crud;
 pub mod mongo_crud;
+pub mod mongo_social_recovery_crud;
+pub mod mongo_token_crud;
 pub mod mongo_user_crud;
 pub mod poll_crud;
+pub mod social_recovery_crud;
+pub mod token_crud;
 pub mod user_crud;
 use crate::db::{mongo_crud::MongoPollRepo, poll_crud::PollRepository};
 
-use config::DbConfig;
+use audit_crud::AuditRepository;
+use ceremony_crud::CeremonyStateRepository;
+use config::{CeremonyStore, DbBackend, DbConfig};
+use memory_ceremony_crud::MemoryCeremonyRepo;
+use mongo_audit_crud::MongoAuditRepo;
+use mongo_ceremony_crud::MongoCeremonyRepo;
+use mongo_social_recovery_crud::MongoSocialRecoveryRepo;
+use mongo_token_crud::MongoTokenRepo;
 use mongo_user_crud::MongoUserRepo;
+use social_recovery_crud::SocialRecoveryRepository;
+use std::sync::Arc;
+use std::time::Duration;
+use token_crud::TokenRepository;
 use user_crud::UserRepository;
 
-pub async fn init(config: DbConfig) -> impl PollRepository {
-    match config.db_type.as_str() {
-        "mongodb" => {
-            let repo = MongoPollRepo::new(&config).await; // Await the Future to get MongoPollRepo
-            repo // Box the resulting MongoPollRepo
-        }
-        _ => panic!("Unsupported database type"),
+pub async fn init(config: DbConfig) -> Result<impl PollRepository, Box<dyn std::error::Error>> {
+    match config.backend {
+        DbBackend::MongoDb => MongoPollRepo::new(&config).await,
     }
 }
 
-pub async fn init_user_db(config: DbConfig) -> impl UserRepository {
-    match config.db_type.as_str() {
-        "mongodb" => {
-            let repo = MongoUserRepo::new(&config).await;
-            repo
-        }
-        _ => panic!("Unsupported database type"),
+pub async fn init_user_db(
+    config: DbConfig,
+) -> Result<impl UserRepository, Box<dyn std::error::Error>> {
+    match config.backend {
+        DbBackend::MongoDb => Ok(MongoUserRepo::new(&config).await?),
+    }
+}
+
+pub async fn init_audit_db(config: DbConfig) -> impl AuditRepository {
+    match config.backend {
+        DbBackend::MongoDb => MongoAuditRepo::new(&config).await,
+    }
+}
+
+pub async fn init_token_db(config: DbConfig) -> impl TokenRepository {
+    match config.backend {
+        DbBackend::MongoDb => MongoTokenRepo::new(&config).await,
+    }
+}
+
+pub async fn init_social_recovery_db(config: DbConfig) -> impl SocialRecoveryRepository {
+    match config.backend {
+        DbBackend::MongoDb => MongoSocialRecoveryRepo::new(&config).await,
+    }
+}
+
+/// Ceremony state can live in memory even when everything else is in MongoDB,
+/// so the store is chosen separately from `config.backend`.
+pub async fn init_ceremony_db(
+    store: CeremonyStore,
+    config: DbConfig,
+    ttl: Duration,
+) -> Arc<dyn CeremonyStateRepository> {
+    match store {
+        CeremonyStore::MongoDb => Arc::new(MongoCeremonyRepo::new(&config, ttl).await),
+        CeremonyStore::Memory => Arc::new(MemoryCeremonyRepo::new(ttl)),
     }
 }
@@ -0,0 +1,36 @@
+use crate::db::{audit_crud::AuditRepository, config::DbConfig};
+use crate::models::audit::AuditEvent;
+
+use mongodb::{Client, Collection};
+
+#[derive(Clone)]
+pub struct MongoAuditRepo {
+    collection: Collection<AuditEvent>,
+}
+
+impl MongoAuditRepo {
+    pub async fn new(config: &DbConfig) -> Self {
+        // Create a MongoDB client
+        let client = Client::with_uri_str(&config.connection_string)
+            .await
+            .expect("Failed to initialize MongoDB client");
+
+        // Get the specified database and collection
+        let database = client.database(&config.database_name);
+        let collection = database.collection("audit_log");
+
+        MongoAuditRepo { collection }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditRepository for MongoAuditRepo {
+    async fn record(
+        &self,
+        event: AuditEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        log::warn!("Audit: {:?}", event);
+        self.collection.insert_one(event, None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use crate::db::{ceremony_crud::CeremonyStateRepository, config::DbConfig};
+use crate::models::ceremony::{AuthenticationCeremony, DiscoverableCeremony, RegistrationCeremony};
+
+use mongodb::bson::{doc, DateTime};
+use mongodb::{
+    options::IndexOptions,
+    {Client, Collection, IndexModel},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+/// Document wrapper adding the lookup key and the deadline used by the TTL index.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct StoredCeremony<T> {
+    ceremony_id: String,
+    expires_at: DateTime,
+    ceremony: T,
+}
+
+#[derive(Clone)]
+pub struct MongoCeremonyRepo {
+    registrations: Collection<StoredCeremony<RegistrationCeremony>>,
+    authentications: Collection<StoredCeremony<AuthenticationCeremony>>,
+    discoverables: Collection<StoredCeremony<DiscoverableCeremony>>,
+    ttl: Duration,
+}
+
+impl MongoCeremonyRepo {
+    pub async fn new(config: &DbConfig, ttl: Duration) -> Self {
+        // Create a MongoDB client
+        let client = Client::with_uri_str(&config.connection_string)
+            .await
+            .expect("Failed to initialize MongoDB client");
+
+        // Get the specified database and collections
+        let database = client.database(&config.database_name);
+        let registrations = database.collection("registration_ceremonies");
+        let authentications = database.collection("authentication_ceremonies");
+        let discoverables = database.collection("discoverable_ceremonies");
+
+        // Let MongoDB reap abandoned ceremonies once their deadline has passed
+        create_ttl_index(&registrations).await;
+        create_ttl_index(&authentications).await;
+        create_ttl_index(&discoverables).await;
+
+        MongoCeremonyRepo {
+            registrations,
+            authentications,
+            discoverables,
+            ttl,
+        }
+    }
+}
+
+async fn create_ttl_index<T: Send + Sync>(collection: &Collection<T>) {
+    let ttl_index = IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(
+            IndexOptions::builder()
+                .expire_after(Duration::from_secs(0))
+                .build(),
+        )
+        .build();
+    let id_index = IndexModel::builder()
+        .keys(doc! { "ceremony_id": 1 })
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    collection
+        .create_indexes([ttl_index, id_index], None)
+        .await
+        .expect("Failed to create ceremony indexes");
+}
+
+async fn insert<T>(
+    collection: &Collection<StoredCeremony<T>>,
+    ceremony: T,
+    ttl: Duration,
+) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: Serialize + Send + Sync,
+{
+    let ceremony_id = Uuid::new_v4();
+    let stored = StoredCeremony {
+        ceremony_id: ceremony_id.to_string(),
+        expires_at: DateTime::from_system_time(SystemTime::now() + ttl),
+        ceremony,
+    };
+    collection.insert_one(stored, None).await?;
+    Ok(ceremony_id)
+}
+
+async fn take<T>(
+    collection: &Collection<StoredCeremony<T>>,
+    ceremony_id: Uuid,
+) -> Result<Option<T>, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    // The TTL monitor only runs periodically, so expiry is also checked here
+    let filter = doc! {
+        "ceremony_id": ceremony_id.to_string(),
+        "expires_at": { "$gt": DateTime::now() },
+    };
+    let stored = collection.find_one_and_delete(filter, None).await?;
+    Ok(stored.map(|stored| stored.ceremony))
+}
+
+async fn sweep<T>(
+    collection: &Collection<StoredCeremony<T>>,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>>
+where
+    T: Send + Sync,
+{
+    let filter = doc! { "expires_at": { "$lte": DateTime::now() } };
+    let result = collection.delete_many(filter, None).await?;
+    Ok(result.deleted_count)
+}
+
+#[async_trait::async_trait]
+impl CeremonyStateRepository for MongoCeremonyRepo {
+    async fn insert_registration(
+        &self,
+        ceremony: RegistrationCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        insert(&self.registrations, ceremony, self.ttl).await
+    }
+
+    async fn take_registration(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<RegistrationCeremony>, Box<dyn std::error::Error + Send + Sync>> {
+        take(&self.registrations, ceremony_id).await
+    }
+
+    async fn insert_authentication(
+        &self,
+        ceremony: AuthenticationCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        insert(&self.authentications, ceremony, self.ttl).await
+    }
+
+    async fn take_authentication(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<AuthenticationCeremony>, Box<dyn std::error::Error + Send + Sync>> {
+        take(&self.authentications, ceremony_id).await
+    }
+
+    async fn insert_discoverable(
+        &self,
+        ceremony: DiscoverableCeremony,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        insert(&self.discoverables, ceremony, self.ttl).await
+    }
+
+    async fn take_discoverable(
+        &self,
+        ceremony_id: Uuid,
+    ) -> Result<Option<DiscoverableCeremony>, Box<dyn std::error::Error + Send + Sync>> {
+        take(&self.discoverables, ceremony_id).await
+    }
+
+    async fn sweep_expired(&self) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(sweep(&self.registrations).await?
+            + sweep(&self.authentications).await?
+            + sweep(&self.discoverables).await?)
+    }
+}
@@ -1,20 +1,71 @@
 use crate::db::{config::DbConfig, poll_crud::PollRepository};
-use crate::models::poll::Poll;
+use crate::models::poll::{
+    Ballot, BallotChoice, CreatePollRequest, Poll, PollStatus, TransitionOutcome,
+    UpdatePollRequest, Viewer, VoteOutcome,
+};
+use crate::models::user::Votes;
 
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
-use mongodb::bson::doc;
+use mongodb::bson::{self, doc, Document};
+use mongodb::error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT};
 use mongodb::{
-    options::UpdateOptions,
-    {Client, Collection},
+    options::{
+        FindOneAndUpdateOptions, FindOneOptions, IndexOptions, ReturnDocument, UpdateOptions,
+    },
+    {Client, ClientSession, Collection, Database, IndexModel},
 };
 
+/// `_id` of the counter document poll IDs are allocated from
+const POLL_ID_COUNTER: &str = "poll_id";
+
+/// The `$inc` that adds (`direction` 1) or takes away (-1) `user_id`'s vote on the options
+/// matched by the array filter `identifier`, weighted as the poll's roll says.
+fn counter_change(poll: &Poll, user_id: &str, identifier: &str, direction: i64) -> Document {
+    let mut inc = doc! { format!("options.$[{}].votes", identifier): direction };
+    if poll.voter_roll.is_some() {
+        let weight = i64::from(poll.weight_of(user_id).unwrap_or(0));
+        inc.insert(
+            format!("options.$[{}].weight", identifier),
+            direction * weight,
+        );
+    }
+    inc
+}
+
+/// What a vote transaction does to a user's ballot in one poll.
+#[derive(Debug, Clone)]
+enum BallotChange {
+    /// A first ballot
+    Cast(BallotChoice),
+    /// Replaces the user's ballot with this one
+    Move(BallotChoice),
+    Retract,
+}
+
+/// A write that must keep poll counts and voting histories in step.
+#[derive(Debug, Clone, Copy)]
+enum VoteWrite<'a> {
+    /// A change to one user's ballot
+    Ballot(&'a str, &'a BallotChange),
+    /// Discards every ballot in the poll
+    Reset,
+}
+
 #[derive(Clone)]
 pub struct MongoPollRepo {
+    client: Client,
     collection: Collection<Poll>,
+    counters: Collection<Document>,
+    /// Individual ballots, for polls that tally from them rather than from counters
+    ballots: Collection<Ballot>,
+    /// The users collection, written together with the poll when a vote is cast
+    users: Collection<Document>,
 }
 
 impl MongoPollRepo {
-    pub async fn new(config: &DbConfig) -> Self {
+    /// Fails when the server cannot run the transactions votes are written in.
+    pub async fn new(config: &DbConfig) -> Result<Self, Box<dyn std::error::Error>> {
         // Create a MongoDB client
         let client = Client::with_uri_str(&config.connection_string)
             .await
@@ -22,39 +73,565 @@ impl MongoPollRepo {
 
         // Get the specified database and collection
         let database = client.database(&config.database_name);
-        let collection = database.collection("polls");
+        ensure_transactions(&database).await?;
+        let collection: Collection<Poll> = database.collection("polls");
+        let counters: Collection<Document> = database.collection("counters");
+        let users: Collection<Document> = database.collection("users");
+        let ballots: Collection<Ballot> = database.collection("ballots");
+
+        // Runs before anything reads polls, as older statuses would not deserialise
+        migrate_statuses(&collection.clone_with_type())
+            .await
+            .expect("Failed to migrate poll statuses");
+        migrate_dates(&collection.clone_with_type())
+            .await
+            .expect("Failed to migrate poll dates");
+        migrate_weights(&collection.clone_with_type())
+            .await
+            .expect("Failed to migrate option weights");
+
+        collection
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "poll_id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                None,
+            )
+            .await
+            .expect("Failed to create poll indexes; are there polls with duplicate IDs?");
+        ballots
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "poll_id": 1, "user_id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                None,
+            )
+            .await
+            .expect("Failed to create ballot indexes");
+
+        // Start the counter past any polls created before IDs were allocated here
+        let newest = collection
+            .find_one(
+                None,
+                FindOneOptions::builder()
+                    .sort(doc! { "poll_id": -1 })
+                    .build(),
+            )
+            .await
+            .expect("Failed to read existing poll IDs");
+        let highest = newest.map_or(0, |poll| poll.poll_id);
+        counters
+            .update_one(
+                doc! { "_id": POLL_ID_COUNTER },
+                doc! { "$max": { "seq": highest } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .expect("Failed to initialise the poll ID counter");
+
+        Ok(MongoPollRepo {
+            client,
+            collection,
+            counters,
+            ballots,
+            users,
+        })
+    }
+
+    /// Runs `write` in a transaction so the poll counts and the voters' histories move together.
+    /// Retries the whole transaction on transient errors, and the commit alone when its outcome
+    /// is unknown, as the MongoDB transaction guidelines describe.
+    async fn in_vote_transaction(
+        &self,
+        poll_id: i64,
+        write: VoteWrite<'_>,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>> {
+        let mut session = self.client.start_session(None).await?;
+        'transaction: loop {
+            session.start_transaction(None).await?;
+            let applied = match write {
+                VoteWrite::Ballot(user_id, change) => {
+                    self.apply_ballot_change(&mut session, poll_id, user_id, change)
+                        .await
+                }
+                VoteWrite::Reset => self.reset(&mut session, poll_id).await,
+            };
+            match applied {
+                Ok(VoteOutcome::Recorded) => {}
+                Ok(outcome) => {
+                    session.abort_transaction().await?;
+                    return Ok(outcome);
+                }
+                Err(e) => {
+                    let _ = session.abort_transaction().await;
+                    if e.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+                        continue 'transaction;
+                    }
+                    return Err(Box::new(e));
+                }
+            }
+
+            loop {
+                match session.commit_transaction().await {
+                    Ok(()) => return Ok(VoteOutcome::Recorded),
+                    Err(e) if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) => continue,
+                    Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) => {
+                        continue 'transaction
+                    }
+                    Err(e) => return Err(Box::new(e)),
+                }
+            }
+        }
+    }
+
+    /// Refuses any change once the deadline has passed, even if the sweeper has not yet marked
+    /// the poll expired, and any ballot that does not suit the poll, then applies `change`.
+    /// Anything but `Recorded` means nothing was written.
+    async fn apply_ballot_change(
+        &self,
+        session: &mut ClientSession,
+        poll_id: i64,
+        user_id: &str,
+        change: &BallotChange,
+    ) -> mongodb::error::Result<VoteOutcome> {
+        let Some(poll) = self
+            .collection
+            .find_one_with_session(doc! { "poll_id": poll_id }, None, session)
+            .await?
+        else {
+            return Ok(VoteOutcome::PollNotFound);
+        };
+        let now = Utc::now();
+        if poll.is_past_deadline(now) {
+            return Ok(VoteOutcome::PollExpired);
+        }
+        if poll.is_due_to_open(now) {
+            // Open a poll whose time has come, even if the sweeper has not got to it yet
+            let filter = doc! {
+                "poll_id": poll_id,
+                "status": PollStatus::Scheduled.as_str(),
+            };
+            let update = doc! { "$set": { "status": PollStatus::Active.as_str() } };
+            self.collection
+                .update_one_with_session(filter, update, None, session)
+                .await?;
+        } else if poll.status == PollStatus::Scheduled {
+            return Ok(VoteOutcome::NotYetOpen);
+        }
+
+        match change {
+            BallotChange::Cast(choice) | BallotChange::Move(choice) => {
+                if poll.weight_of(user_id).is_none() {
+                    return Ok(VoteOutcome::NotOnRoll);
+                }
+                if let Err(problem) = poll.check_ballot(choice) {
+                    return Ok(VoteOutcome::InvalidBallot(problem));
+                }
+            }
+            BallotChange::Retract => {}
+        }
+
+        match change {
+            BallotChange::Cast(choice) => self.cast(session, &poll, choice, user_id).await,
+            BallotChange::Move(choice) => self.move_vote(session, &poll, choice, user_id).await,
+            BallotChange::Retract => self.retract(session, &poll, user_id).await,
+        }
+    }
+
+    /// Counts a first ballot, adding one to every option it selects or storing it whole for
+    /// polls that keep their ballots.
+    async fn cast(
+        &self,
+        session: &mut ClientSession,
+        poll: &Poll,
+        choice: &BallotChoice,
+        user_id: &str,
+    ) -> mongodb::error::Result<VoteOutcome> {
+        let poll_id = poll.poll_id;
+        let option_ids = choice.option_ids();
+        let keeps_ballots = poll.voting_method.keeps_ballots();
+        // Matching only when the user is not yet in `users_voted` makes a second vote a no-op
+        let filter = doc! {
+            "poll_id": poll_id,
+            "status": PollStatus::Active.as_str(),
+            "users_voted": { "$ne": user_id },
+        };
+        let mut update = doc! { "$push": { "users_voted": user_id } };
+        let mut options = None;
+        if !keeps_ballots {
+            update.insert("$inc", counter_change(poll, user_id, "chosen", 1));
+            options = Some(
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! { "chosen.option_id": { "$in": &option_ids } }])
+                    .build(),
+            );
+        }
+        let result = self
+            .collection
+            .update_one_with_session(filter, update, options, session)
+            .await?;
+
+        if result.matched_count == 0 {
+            let poll = self
+                .collection
+                .find_one_with_session(doc! { "poll_id": poll_id }, None, session)
+                .await?;
+            return Ok(match poll {
+                None => VoteOutcome::PollNotFound,
+                Some(poll) if poll.users_voted.iter().any(|voter| voter == user_id) => {
+                    VoteOutcome::AlreadyVoted
+                }
+                Some(_) => VoteOutcome::PollNotActive,
+            });
+        }
+
+        if keeps_ballots {
+            let ballot = Ballot::new(poll_id, user_id, choice, Utc::now());
+            self.ballots
+                .insert_one_with_session(ballot, None, session)
+                .await?;
+        }
+
+        // Older user documents have a null history, which `$push` cannot append to
+        let vote = bson::to_bson(&Votes::new(poll_id, option_ids))?;
+        let update = vec![doc! {
+            "$set": { "polls_voted": {
+                "$concatArrays": [{ "$ifNull": ["$polls_voted", []] }, [vote]]
+            } }
+        }];
+        let result = self
+            .users
+            .update_one_with_session(doc! { "user_id": user_id }, update, None, session)
+            .await?;
+        if result.matched_count == 0 {
+            return Err(mongodb::error::Error::custom(format!(
+                "voter {} has no account",
+                user_id
+            )));
+        }
+
+        Ok(VoteOutcome::Recorded)
+    }
+
+    /// Replaces an existing ballot, moving counts only for the options that changed, or
+    /// rewriting the stored ballot for polls that keep theirs.
+    async fn move_vote(
+        &self,
+        session: &mut ClientSession,
+        poll: &Poll,
+        choice: &BallotChoice,
+        user_id: &str,
+    ) -> mongodb::error::Result<VoteOutcome> {
+        let poll_id = poll.poll_id;
+        let option_ids = choice.option_ids();
+        let Some(previous) = self.current_vote(session, poll_id, user_id).await? else {
+            return self.explain_change_refusal(session, poll_id).await;
+        };
+        let filter = doc! {
+            "poll_id": poll_id,
+            "status": PollStatus::Active.as_str(),
+            "allow_vote_changes": { "$ne": false },
+            "users_voted": user_id,
+        };
+
+        if poll.voting_method.keeps_ballots() {
+            // The counts live in the ballots, so the poll itself is left as it is
+            let open = self
+                .collection
+                .find_one_with_session(filter, None, session)
+                .await?;
+            if open.is_none() {
+                return self.explain_change_refusal(session, poll_id).await;
+            }
+            let ballot = Ballot::new(poll_id, user_id, choice, Utc::now());
+            let update = doc! { "$set": {
+                "ranking": &ballot.ranking,
+                "scores": bson::to_bson(&ballot.scores)?,
+                "cast_at": bson::to_bson(&ballot.cast_at)?,
+            } };
+            self.ballots
+                .update_one_with_session(
+                    doc! { "poll_id": poll_id, "user_id": user_id },
+                    update,
+                    None,
+                    session,
+                )
+                .await?;
+        } else {
+            // An option in both ballots keeps its count; touching it twice would be a conflict
+            let removed: Vec<i64> = previous
+                .iter()
+                .copied()
+                .filter(|id| !option_ids.contains(id))
+                .collect();
+            let added: Vec<i64> = option_ids
+                .iter()
+                .copied()
+                .filter(|id| !previous.contains(id))
+                .collect();
+            if removed.is_empty() && added.is_empty() {
+                return Ok(VoteOutcome::Recorded);
+            }
 
-        MongoPollRepo { collection }
+            let mut inc = Document::new();
+            let mut array_filters = Vec::new();
+            if !removed.is_empty() {
+                inc.extend(counter_change(poll, user_id, "removed", -1));
+                array_filters.push(doc! { "removed.option_id": { "$in": removed } });
+            }
+            if !added.is_empty() {
+                inc.extend(counter_change(poll, user_id, "added", 1));
+                array_filters.push(doc! { "added.option_id": { "$in": added } });
+            }
+            let options = UpdateOptions::builder()
+                .array_filters(array_filters)
+                .build();
+            let result = self
+                .collection
+                .update_one_with_session(filter, doc! { "$inc": inc }, options, session)
+                .await?;
+            if result.matched_count == 0 {
+                return self.explain_change_refusal(session, poll_id).await;
+            }
+        }
+
+        let update = doc! { "$set": {
+            "polls_voted.$[vote].option_id": option_ids[0],
+            "polls_voted.$[vote].option_ids": option_ids,
+        } };
+        let options = UpdateOptions::builder()
+            .array_filters(vec![doc! { "vote.poll_id": poll_id }])
+            .build();
+        self.users
+            .update_one_with_session(doc! { "user_id": user_id }, update, options, session)
+            .await?;
+
+        Ok(VoteOutcome::Recorded)
+    }
+
+    /// Withdraws a ballot, leaving the user free to vote again.
+    async fn retract(
+        &self,
+        session: &mut ClientSession,
+        poll: &Poll,
+        user_id: &str,
+    ) -> mongodb::error::Result<VoteOutcome> {
+        let poll_id = poll.poll_id;
+        let keeps_ballots = poll.voting_method.keeps_ballots();
+        let Some(previous) = self.current_vote(session, poll_id, user_id).await? else {
+            return self.explain_change_refusal(session, poll_id).await;
+        };
+
+        let filter = doc! {
+            "poll_id": poll_id,
+            "status": PollStatus::Active.as_str(),
+            "allow_vote_changes": { "$ne": false },
+            "users_voted": user_id,
+        };
+        let mut update = doc! { "$pull": { "users_voted": user_id } };
+        let mut options = None;
+        if !keeps_ballots {
+            update.insert("$inc", counter_change(poll, user_id, "previous", -1));
+            options = Some(
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! { "previous.option_id": { "$in": previous } }])
+                    .build(),
+            );
+        }
+        let result = self
+            .collection
+            .update_one_with_session(filter, update, options, session)
+            .await?;
+        if result.matched_count == 0 {
+            return self.explain_change_refusal(session, poll_id).await;
+        }
+
+        if keeps_ballots {
+            self.ballots
+                .delete_one_with_session(
+                    doc! { "poll_id": poll_id, "user_id": user_id },
+                    None,
+                    session,
+                )
+                .await?;
+        }
+
+        let update = doc! { "$pull": { "polls_voted": { "poll_id": poll_id } } };
+        self.users
+            .update_one_with_session(doc! { "user_id": user_id }, update, None, session)
+            .await?;
+
+        Ok(VoteOutcome::Recorded)
+    }
+
+    /// The options the user's history says they selected in this poll.
+    async fn current_vote(
+        &self,
+        session: &mut ClientSession,
+        poll_id: i64,
+        user_id: &str,
+    ) -> mongodb::error::Result<Option<Vec<i64>>> {
+        let user = self
+            .users
+            .find_one_with_session(doc! { "user_id": user_id }, None, session)
+            .await?;
+        let history = user
+            .and_then(|user| user.get_array("polls_voted").ok().cloned())
+            .unwrap_or_default();
+        Ok(history
+            .iter()
+            .filter_map(|vote| bson::from_bson::<Votes>(vote.clone()).ok())
+            .find(|vote| vote.poll_id == poll_id)
+            .map(|vote| vote.selection()))
+    }
+
+    /// Zeroes the counts, forgets who voted and discards the ballots, taking the poll out of every
+    /// voter's history so they can vote again.
+    async fn reset(
+        &self,
+        session: &mut ClientSession,
+        poll_id: i64,
+    ) -> mongodb::error::Result<VoteOutcome> {
+        let update = doc! { "$set": {
+            "options.$[].votes": 0,
+            "users_voted": [],
+        } };
+        let result = self
+            .collection
+            .update_one_with_session(doc! { "poll_id": poll_id }, update, None, session)
+            .await?;
+        if result.matched_count == 0 {
+            return Ok(VoteOutcome::PollNotFound);
+        }
+        self.collection
+            .update_one_with_session(
+                doc! { "poll_id": poll_id, "voter_roll": { "$type": "object" } },
+                doc! { "$set": { "options.$[].weight": 0_i64 } },
+                None,
+                session,
+            )
+            .await?;
+
+        self.users
+            .update_many_with_session(
+                doc! { "polls_voted.poll_id": poll_id },
+                doc! { "$pull": { "polls_voted": { "poll_id": poll_id } } },
+                None,
+                session,
+            )
+            .await?;
+        self.ballots
+            .delete_many_with_session(doc! { "poll_id": poll_id }, None, session)
+            .await?;
+
+        Ok(VoteOutcome::Recorded)
+    }
+
+    /// Works out why a change or retraction matched nothing.
+    async fn explain_change_refusal(
+        &self,
+        session: &mut ClientSession,
+        poll_id: i64,
+    ) -> mongodb::error::Result<VoteOutcome> {
+        let poll = self
+            .collection
+            .find_one_with_session(doc! { "poll_id": poll_id }, None, session)
+            .await?;
+        Ok(match poll {
+            None => VoteOutcome::PollNotFound,
+            Some(poll) if poll.status != PollStatus::Active => VoteOutcome::PollNotActive,
+            Some(poll) if !poll.allow_vote_changes => VoteOutcome::ChangesDisabled,
+            Some(_) => VoteOutcome::NotVoted,
+        })
+    }
+
+    /// Moves the poll to `to` if its current status allows it, setting `extra` alongside.
+    async fn transition(
+        &self,
+        poll_id: i64,
+        to: PollStatus,
+        mut extra: Document,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>> {
+        let from: Vec<&str> = to.allowed_from().iter().map(|s| s.as_str()).collect();
+        let filter = doc! { "poll_id": poll_id, "status": { "$in": from } };
+        extra.insert("status", to.as_str());
+        let result = self
+            .collection
+            .update_one(filter, doc! { "$set": extra }, None)
+            .await?;
+        if result.matched_count > 0 {
+            return Ok(TransitionOutcome::Done);
+        }
+
+        Ok(
+            match self
+                .collection
+                .find_one(doc! { "poll_id": poll_id }, None)
+                .await?
+            {
+                Some(poll) => TransitionOutcome::Illegal(poll.status),
+                None => TransitionOutcome::PollNotFound,
+            },
+        )
+    }
+
+    /// Atomically hands out the next poll ID; 0 is never used as it means "all polls".
+    async fn next_poll_id(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+        let counter = self
+            .counters
+            .find_one_and_update(
+                doc! { "_id": POLL_ID_COUNTER },
+                doc! { "$inc": { "seq": 1_i64 } },
+                options,
+            )
+            .await?
+            .ok_or("poll ID counter is missing")?;
+        Ok(counter.get_i64("seq")?)
     }
 }
 
 #[async_trait::async_trait]
 impl PollRepository for MongoPollRepo {
-    async fn create_poll(&self, poll: Poll) -> Result<Poll, Box<dyn std::error::Error>> {
-        println!("Entered Create Poll Func");
-        println!("{:#?}", poll);
+    async fn create_poll(
+        &self,
+        request: CreatePollRequest,
+        creator: String,
+    ) -> Result<Poll, Box<dyn std::error::Error>> {
+        let poll = request.into_poll(self.next_poll_id().await?, creator, Utc::now());
         match self.collection.insert_one(poll.clone(), None).await {
-            Ok(insert_one_result) => {
-                println!("Added Poll Successfully {}", insert_one_result.inserted_id)
-            }
+            Ok(_) => log::debug!("Added poll {}", poll.poll_id),
             Err(e) => {
-                eprintln!("Failed adding polls to db {}", e)
+                eprintln!("Failed adding polls to db {}", e);
+                return Err(Box::new(e));
             }
         }
 
         Ok(poll)
     }
 
-    async fn fetch_all(&self) -> Result<Vec<Poll>, Box<dyn std::error::Error>> {
-        match self.collection.find(None, None).await {
-            Ok(polls) => {
-                let poll_vec = polls.try_collect().await?;
-                println!("{:?}", poll_vec);
-                println!("dgfgdg");
-                Ok(poll_vec)
-            }
+    async fn fetch_all(&self, viewer: Viewer) -> Result<Vec<Poll>, Box<dyn std::error::Error>> {
+        let unpublished: Vec<&str> = PollStatus::ALL
+            .iter()
+            .filter(|status| !status.is_published())
+            .map(|status| status.as_str())
+            .collect();
+        let filter = match viewer {
+            Viewer::Admin => None,
+            Viewer::Member(user_id) => Some(doc! { "$or": [
+                { "status": { "$nin": unpublished } },
+                { "creator": user_id },
+            ] }),
+            Viewer::Anonymous => Some(doc! { "status": { "$nin": unpublished } }),
+        };
+        match self.collection.find(filter, None).await {
+            Ok(polls) => Ok(polls.try_collect().await?),
             Err(e) => {
-                println!("dgfghjfhgjfgmfgdg");
                 eprintln!("Error retrieving poll: {:?}", e);
                 Err(Box::new(e))
             }
@@ -65,8 +642,6 @@ impl PollRepository for MongoPollRepo {
         &self,
         poll_id: i64,
     ) -> Result<Option<Poll>, Box<dyn std::error::Error + Send + Sync>> {
-        println!("Entered get_poll");
-
         let filter = doc! { "poll_id": poll_id };
 
         match self.collection.find_one(filter, None).await {
@@ -82,62 +657,303 @@ impl PollRepository for MongoPollRepo {
         }
     }
 
-    async fn update_poll(
+    async fn reset_votes(&self, poll_id: i64) -> Result<(), Box<dyn std::error::Error>> {
+        self.in_vote_transaction(poll_id, VoteWrite::Reset).await?;
+        Ok(())
+    }
+
+    async fn schedule_poll(
         &self,
         poll_id: i64,
-        target: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let filter = doc! { "poll_id": poll_id };
-        let update;
-        if target == "reset" {
-            update = doc! {
-                  "$set": { "options.$[].votes": 0 }
-            };
-        } else {
-            update = doc! {
-                "$set": { "status": "closed" }
-            }
-        }
+        opens_at: DateTime<Utc>,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>> {
+        let opening = doc! { "opens_at": bson_date(opens_at) };
+        self.transition(poll_id, PollStatus::Scheduled, opening)
+            .await
+    }
 
-        self.collection.update_one(filter, update, None).await?;
-        Ok(()) // Return the updated poll
+    async fn activate_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>> {
+        let opening = doc! { "opens_at": bson_date(Utc::now()) };
+        self.transition(poll_id, PollStatus::Active, opening).await
+    }
+
+    async fn close_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>> {
+        self.transition(poll_id, PollStatus::Closed, Document::new())
+            .await
+    }
+
+    async fn expire_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>> {
+        let deadline = doc! { "expiration_date": bson_date(Utc::now()) };
+        self.transition(poll_id, PollStatus::Expired, deadline)
+            .await
+    }
+
+    async fn archive_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>> {
+        self.transition(poll_id, PollStatus::Archived, Document::new())
+            .await
+    }
+
+    async fn ballots(
+        &self,
+        poll_id: i64,
+    ) -> Result<Vec<Ballot>, Box<dyn std::error::Error + Send + Sync>> {
+        let ballots = self
+            .ballots
+            .find(doc! { "poll_id": poll_id }, None)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(ballots)
     }
 
     async fn delete_poll(&self, poll_id: i64) -> Result<(), Box<dyn std::error::Error>> {
         let filter = doc! { "poll_id": poll_id };
         self.collection.delete_one(filter, None).await?;
+        self.ballots
+            .delete_many(doc! { "poll_id": poll_id }, None)
+            .await?;
         Ok(())
     }
 
     async fn vote_poll(
         &self,
         poll_id: i64,
-        option_id: i64,
-        username: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let filter = doc! { "poll_id": poll_id, "status": "active" };
-        let update = doc! {
-            "$inc": { "options.$[elem].votes": 1 },
-            "$push": {
-                "users_voted": username
+        choice: BallotChoice,
+        user_id: String,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>> {
+        self.in_vote_transaction(
+            poll_id,
+            VoteWrite::Ballot(&user_id, &BallotChange::Cast(choice)),
+        )
+        .await
+    }
+
+    async fn change_vote(
+        &self,
+        poll_id: i64,
+        choice: BallotChoice,
+        user_id: String,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>> {
+        self.in_vote_transaction(
+            poll_id,
+            VoteWrite::Ballot(&user_id, &BallotChange::Move(choice)),
+        )
+        .await
+    }
+
+    async fn retract_vote(
+        &self,
+        poll_id: i64,
+        user_id: String,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>> {
+        self.in_vote_transaction(poll_id, VoteWrite::Ballot(&user_id, &BallotChange::Retract))
+            .await
+    }
+
+    async fn update_poll(
+        &self,
+        poll_id: i64,
+        update: UpdatePollRequest,
+    ) -> Result<Option<Poll>, Box<dyn std::error::Error>> {
+        let editable: Vec<&str> = update.editable_in().iter().map(|s| s.as_str()).collect();
+        let filter = doc! { "poll_id": poll_id, "status": { "$in": editable } };
+
+        let mut set = Document::new();
+        if let Some(title) = &update.title {
+            set.insert("title", title);
+        }
+        if let Some(description) = &update.description {
+            set.insert("description", description);
+        }
+        if let Some(options) = update.new_options() {
+            set.insert("options", bson::to_bson(&options)?);
+        }
+        if let Some(voting_method) = update.voting_method {
+            set.insert("voting_method", bson::to_bson(&voting_method)?);
+        }
+        if let Some(min_selections) = update.min_selections {
+            set.insert("min_selections", min_selections);
+        }
+        if let Some(max_selections) = update.max_selections {
+            set.insert("max_selections", max_selections);
+        }
+        if let Some(min_score) = update.min_score {
+            set.insert("min_score", min_score);
+        }
+        if let Some(max_score) = update.max_score {
+            set.insert("max_score", max_score);
+        }
+        if let Some(star_runoff) = update.star_runoff {
+            set.insert("star_runoff", star_runoff);
+        }
+        if let Some(voter_roll) = &update.voter_roll {
+            set.insert("voter_roll", bson::to_bson(voter_roll)?);
+        }
+        if let Some(allow_vote_changes) = update.allow_vote_changes {
+            set.insert("allow_vote_changes", allow_vote_changes);
+        }
+        if let Some(opens_at) = update.opens_at {
+            set.insert("opens_at", bson_date(opens_at));
+        }
+        if let Some(expiration_date) = update.expiration_date {
+            set.insert("expiration_date", bson_date(expiration_date));
+        }
+        if set.is_empty() {
+            return Ok(self.collection.find_one(filter, None).await?);
+        }
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let mut poll = self
+            .collection
+            .find_one_and_update(filter, doc! { "$set": set }, options)
+            .await?;
+
+        // New options on a poll that was already weighted, or a roll added to a poll's existing
+        // options, leave options without a weight; polls in a draft have no votes to weigh
+        if let Some(poll) = poll.as_mut().filter(|poll| poll.voter_roll.is_some()) {
+            if poll.options.iter().any(|option| option.weight.is_none()) {
+                let options = UpdateOptions::builder()
+                    .array_filters(vec![doc! { "unweighted.weight": { "$exists": false } }])
+                    .build();
+                self.collection
+                    .update_one(
+                        doc! { "poll_id": poll_id },
+                        doc! { "$set": { "options.$[unweighted].weight": 0_i64 } },
+                        options,
+                    )
+                    .await?;
+                for option in &mut poll.options {
+                    option.weight.get_or_insert(0);
+                }
             }
+        }
+        Ok(poll)
+    }
+
+    async fn open_due(&self, now: DateTime<Utc>) -> Result<u64, Box<dyn std::error::Error>> {
+        let filter = doc! {
+            "status": PollStatus::Scheduled.as_str(),
+            "opens_at": { "$lte": bson_date(now) },
         };
-        let array_filters = vec![doc! { "elem.option_id": option_id }];
+        let update = doc! { "$set": { "status": PollStatus::Active.as_str() } };
+        let result = self.collection.update_many(filter, update, None).await?;
+        Ok(result.modified_count)
+    }
 
-        let options = UpdateOptions::builder()
-            .array_filters(array_filters)
-            .build();
+    async fn expire_due(&self, now: DateTime<Utc>) -> Result<u64, Box<dyn std::error::Error>> {
+        let filter = doc! {
+            "status": PollStatus::Active.as_str(),
+            "expiration_date": { "$lte": bson_date(now) },
+        };
+        let update = doc! { "$set": { "status": PollStatus::Expired.as_str() } };
+        let result = self.collection.update_many(filter, update, None).await?;
+        Ok(result.modified_count)
+    }
+}
 
-        let result = self.collection.update_one(filter, update, options).await?;
+/// The BSON date for `time`, as polls store their opening time and deadline.
+fn bson_date(time: DateTime<Utc>) -> bson::DateTime {
+    bson::DateTime::from_millis(time.timestamp_millis())
+}
 
-        if result.matched_count == 0 {
-            println!("No matching active poll or option found.");
-        } else if result.modified_count == 0 {
-            println!("Vote not recorded; possibly a conflict.");
-        } else {
-            println!("Vote successfully recorded!");
+/// Rewrites opening times and deadlines stored as RFC 3339 strings, from before they were kept
+/// as BSON dates, so the sweeper's date filters match them.
+async fn migrate_dates(polls: &Collection<Document>) -> mongodb::error::Result<()> {
+    let mut migrated = 0;
+    for field in ["opens_at", "expiration_date"] {
+        let filter = doc! { field: { "$type": "string" } };
+        let stale: Vec<Document> = polls.find(filter, None).await?.try_collect().await?;
+        for poll in stale {
+            let Ok(text) = poll.get_str(field) else {
+                continue;
+            };
+            let Ok(time) = DateTime::parse_from_rfc3339(text) else {
+                log::warn!(
+                    "Poll {:?} has an unreadable {}: {}",
+                    poll.get("poll_id"),
+                    field,
+                    text
+                );
+                continue;
+            };
+            let update = doc! { "$set": { field: bson_date(time.with_timezone(&Utc)) } };
+            polls
+                .update_one(doc! { "_id": poll.get("_id") }, update, None)
+                .await?;
+            migrated += 1;
         }
+    }
+    if migrated > 0 {
+        log::info!("Migrated {} poll dates to BSON dates", migrated);
+    }
+    Ok(())
+}
 
+/// Checks that the server is a replica set member or a mongos, as standalone servers reject
+/// the transactions votes are written in.
+async fn ensure_transactions(database: &Database) -> Result<(), Box<dyn std::error::Error>> {
+    let hello = database.run_command(doc! { "hello": 1 }, None).await?;
+    if hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid") {
         Ok(())
+    } else {
+        Err(
+            "MongoDB must run as a replica set or sharded cluster, as votes are written in \
+             transactions; see database.connection_string in config.example.toml"
+                .into(),
+        )
+    }
+}
+
+/// Drops the zero weights that earlier versions stored on the options of unweighted polls.
+async fn migrate_weights(polls: &Collection<Document>) -> mongodb::error::Result<()> {
+    let filter = doc! { "voter_roll": null, "options.weight": { "$exists": true } };
+    let update = doc! { "$unset": { "options.$[].weight": "" } };
+    let result = polls.update_many(filter, update, None).await?;
+    if result.modified_count > 0 {
+        log::info!(
+            "Removed option weights from {} unweighted polls",
+            result.modified_count
+        );
+    }
+    Ok(())
+}
+
+/// Brings statuses written before `PollStatus` existed into line: free-form values such as
+/// "Active" are lowercased, a missing status counts as active, and anything still unknown is
+/// closed so it stops taking votes.
+async fn migrate_statuses(polls: &Collection<Document>) -> mongodb::error::Result<()> {
+    let known: Vec<&str> = PollStatus::ALL.iter().map(|s| s.as_str()).collect();
+    let filter = doc! { "status": { "$nin": known.clone() } };
+    let lowercase = vec![doc! {
+        "$set": { "status": { "$toLower": { "$ifNull": ["$status", "active"] } } }
+    }];
+    let normalised = polls.update_many(filter.clone(), lowercase, None).await?;
+    let closed = polls
+        .update_many(
+            filter,
+            doc! { "$set": { "status": PollStatus::Closed.as_str() } },
+            None,
+        )
+        .await?;
+    if normalised.modified_count > 0 || closed.modified_count > 0 {
+        log::info!(
+            "Migrated poll statuses: {} normalised, {} unknown closed",
+            normalised.modified_count,
+            closed.modified_count
+        );
     }
+    Ok(())
 }
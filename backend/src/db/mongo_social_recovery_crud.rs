@@ -0,0 +1,262 @@
+use crate::db::{config::DbConfig, social_recovery_crud::SocialRecoveryRepository};
+use crate::models::social_recovery::{RecoverySetup, SocialRecoveryRequest, TrusteeShare};
+
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, DateTime, Document};
+use mongodb::{
+    options::{IndexOptions, ReplaceOptions},
+    {Client, Collection, IndexModel},
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Storage form of a recovery request; the deadline is a BSON date so the TTL index can reap it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct StoredRequest {
+    request: SocialRecoveryRequest,
+    expires_at: DateTime,
+}
+
+#[derive(Clone)]
+pub struct MongoSocialRecoveryRepo {
+    setups: Collection<RecoverySetup>,
+    shares: Collection<TrusteeShare>,
+    requests: Collection<StoredRequest>,
+}
+
+impl MongoSocialRecoveryRepo {
+    pub async fn new(config: &DbConfig) -> Self {
+        // Create a MongoDB client
+        let client = Client::with_uri_str(&config.connection_string)
+            .await
+            .expect("Failed to initialize MongoDB client");
+
+        // Get the specified database and collections
+        let database = client.database(&config.database_name);
+        let setups: Collection<RecoverySetup> = database.collection("recovery_setups");
+        let shares: Collection<TrusteeShare> = database.collection("recovery_shares");
+        let requests: Collection<StoredRequest> = database.collection("social_recovery_requests");
+
+        setups
+            .create_indexes(
+                [
+                    unique_index(doc! { "owner_id": 1 }),
+                    IndexModel::builder()
+                        .keys(doc! { "trustee_ids": 1 })
+                        .build(),
+                ],
+                None,
+            )
+            .await
+            .expect("Failed to create recovery setup indexes");
+        shares
+            .create_indexes(
+                [
+                    unique_index(doc! { "owner_id": 1, "trustee_id": 1 }),
+                    IndexModel::builder().keys(doc! { "trustee_id": 1 }).build(),
+                ],
+                None,
+            )
+            .await
+            .expect("Failed to create recovery share indexes");
+        requests
+            .create_indexes(
+                [
+                    IndexModel::builder()
+                        .keys(doc! { "expires_at": 1 })
+                        .options(
+                            IndexOptions::builder()
+                                .expire_after(Duration::from_secs(0))
+                                .build(),
+                        )
+                        .build(),
+                    unique_index(doc! { "request.request_id": 1 }),
+                    IndexModel::builder()
+                        .keys(doc! { "request.owner_id": 1 })
+                        .build(),
+                ],
+                None,
+            )
+            .await
+            .expect("Failed to create social recovery request indexes");
+
+        MongoSocialRecoveryRepo {
+            setups,
+            shares,
+            requests,
+        }
+    }
+}
+
+fn unique_index(keys: Document) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(true).build())
+        .build()
+}
+
+/// Filter for a live request, checking the requester's secret.
+fn live_request(request_id: String, request_secret_hash: String) -> Document {
+    doc! {
+        "request.request_id": request_id,
+        "request.request_secret_hash": request_secret_hash,
+        "expires_at": { "$gt": DateTime::now() },
+    }
+}
+
+#[async_trait::async_trait]
+impl SocialRecoveryRepository for MongoSocialRecoveryRepo {
+    async fn set_trustees(
+        &self,
+        setup: RecoverySetup,
+        shares: Vec<TrusteeShare>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let owner = doc! { "owner_id": setup.owner_id.clone() };
+        // Requests in flight and uncollected shares belong to the old secret, which no longer
+        // verifies
+        self.requests
+            .delete_many(doc! { "request.owner_id": setup.owner_id.clone() }, None)
+            .await?;
+        self.shares.delete_many(owner.clone(), None).await?;
+        self.shares.insert_many(shares, None).await?;
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.setups.replace_one(owner, setup, options).await?;
+        Ok(())
+    }
+
+    async fn get_setup(
+        &self,
+        owner_id: String,
+    ) -> Result<Option<RecoverySetup>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self
+            .setups
+            .find_one(doc! { "owner_id": owner_id }, None)
+            .await?)
+    }
+
+    async fn remove_trustees(
+        &self,
+        owner_id: String,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let owner = doc! { "owner_id": owner_id.clone() };
+        let result = self.setups.delete_one(owner.clone(), None).await?;
+        self.shares.delete_many(owner, None).await?;
+        self.requests
+            .delete_many(doc! { "request.owner_id": owner_id }, None)
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn take_shares(
+        &self,
+        trustee_id: String,
+    ) -> Result<Vec<TrusteeShare>, Box<dyn std::error::Error + Send + Sync>> {
+        // One at a time, so a share queued by a concurrent setup is never deleted unseen
+        let mut shares = vec![];
+        while let Some(share) = self
+            .shares
+            .find_one_and_delete(doc! { "trustee_id": trustee_id.clone() }, None)
+            .await?
+        {
+            shares.push(share);
+        }
+        Ok(shares)
+    }
+
+    async fn create_request(
+        &self,
+        request: SocialRecoveryRequest,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let stored = StoredRequest {
+            expires_at: DateTime::from_millis(request.expires_at.timestamp_millis()),
+            request,
+        };
+        self.requests.insert_one(stored, None).await?;
+        Ok(())
+    }
+
+    async fn pending_requests_for_trustee(
+        &self,
+        trustee_id: String,
+    ) -> Result<Vec<SocialRecoveryRequest>, Box<dyn std::error::Error + Send + Sync>> {
+        let setups: Vec<RecoverySetup> = self
+            .setups
+            .find(doc! { "trustee_ids": trustee_id }, None)
+            .await?
+            .try_collect()
+            .await?;
+        let owner_ids: Vec<String> = setups.into_iter().map(|setup| setup.owner_id).collect();
+        if owner_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let filter = doc! {
+            "request.owner_id": { "$in": owner_ids },
+            "expires_at": { "$gt": DateTime::now() },
+        };
+        let stored: Vec<StoredRequest> = self
+            .requests
+            .find(filter, None)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(stored.into_iter().map(|stored| stored.request).collect())
+    }
+
+    async fn approve_request(
+        &self,
+        request_id: String,
+        trustee_id: String,
+        share: String,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let filter = doc! {
+            "request.request_id": request_id.clone(),
+            "expires_at": { "$gt": DateTime::now() },
+        };
+        let Some(stored) = self.requests.find_one(filter, None).await? else {
+            return Ok(false);
+        };
+        let setup_filter = doc! {
+            "owner_id": stored.request.owner_id.clone(),
+            "trustee_ids": trustee_id.clone(),
+        };
+        if self.setups.find_one(setup_filter, None).await?.is_none() {
+            return Ok(false);
+        }
+        let share = TrusteeShare {
+            owner_id: stored.request.owner_id,
+            trustee_id: trustee_id.clone(),
+            share,
+        };
+
+        // Only the first approval by each trustee counts
+        let filter = doc! {
+            "request.request_id": request_id,
+            "request.approvals.trustee_id": { "$ne": trustee_id },
+            "expires_at": { "$gt": DateTime::now() },
+        };
+        let update = doc! { "$push": { "request.approvals": bson::to_bson(&share)? } };
+        let result = self.requests.update_one(filter, update, None).await?;
+        Ok(result.modified_count > 0)
+    }
+
+    async fn get_request(
+        &self,
+        request_id: String,
+        request_secret_hash: String,
+    ) -> Result<Option<SocialRecoveryRequest>, Box<dyn std::error::Error + Send + Sync>> {
+        let filter = live_request(request_id, request_secret_hash);
+        let stored = self.requests.find_one(filter, None).await?;
+        Ok(stored.map(|stored| stored.request))
+    }
+
+    async fn take_request(
+        &self,
+        request_id: String,
+        request_secret_hash: String,
+    ) -> Result<Option<SocialRecoveryRequest>, Box<dyn std::error::Error + Send + Sync>> {
+        let filter = live_request(request_id, request_secret_hash);
+        let stored = self.requests.find_one_and_delete(filter, None).await?;
+        Ok(stored.map(|stored| stored.request))
+    }
+}
@@ -0,0 +1,201 @@
+use crate::db::{config::DbConfig, token_crud::TokenRepository};
+use crate::models::token::{RefreshOutcome, RefreshToken};
+
+use chrono::Utc;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::{
+    options::IndexOptions,
+    {Client, Collection, IndexModel},
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Storage form of a refresh token; the deadline is a BSON date so the TTL index can reap it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct StoredRefreshToken {
+    token: RefreshToken,
+    expires_at: DateTime,
+    used: bool,
+    revoked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RevokedToken {
+    jti: String,
+    expires_at: DateTime,
+}
+
+#[derive(Clone)]
+pub struct MongoTokenRepo {
+    refresh_tokens: Collection<StoredRefreshToken>,
+    revoked_tokens: Collection<RevokedToken>,
+}
+
+impl MongoTokenRepo {
+    pub async fn new(config: &DbConfig) -> Self {
+        // Create a MongoDB client
+        let client = Client::with_uri_str(&config.connection_string)
+            .await
+            .expect("Failed to initialize MongoDB client");
+
+        // Get the specified database and collections
+        let database = client.database(&config.database_name);
+        let refresh_tokens: Collection<StoredRefreshToken> = database.collection("refresh_tokens");
+        let revoked_tokens: Collection<RevokedToken> = database.collection("revoked_tokens");
+
+        refresh_tokens
+            .create_indexes(
+                [
+                    ttl_index(),
+                    unique_index(doc! { "token.token_hash": 1 }),
+                    IndexModel::builder()
+                        .keys(doc! { "token.family_id": 1 })
+                        .build(),
+                ],
+                None,
+            )
+            .await
+            .expect("Failed to create refresh token indexes");
+        revoked_tokens
+            .create_indexes([ttl_index(), unique_index(doc! { "jti": 1 })], None)
+            .await
+            .expect("Failed to create revoked token indexes");
+
+        MongoTokenRepo {
+            refresh_tokens,
+            revoked_tokens,
+        }
+    }
+
+    /// Marks the matching refresh tokens revoked and blocks their still-live access tokens.
+    async fn revoke_matching(
+        &self,
+        filter: Document,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.refresh_tokens
+            .update_many(filter.clone(), doc! { "$set": { "revoked": true } }, None)
+            .await?;
+
+        let now = Utc::now();
+        let stored: Vec<StoredRefreshToken> = self
+            .refresh_tokens
+            .find(filter, None)
+            .await?
+            .try_collect()
+            .await?;
+        for stored in stored {
+            if stored.token.access_expires_at > now {
+                self.revoke_access_token(stored.token.access_jti, stored.token.access_expires_at)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn ttl_index() -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(
+            IndexOptions::builder()
+                .expire_after(Duration::from_secs(0))
+                .build(),
+        )
+        .build()
+}
+
+fn unique_index(keys: Document) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(true).build())
+        .build()
+}
+
+#[async_trait::async_trait]
+impl TokenRepository for MongoTokenRepo {
+    async fn store_refresh_token(
+        &self,
+        token: RefreshToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let stored = StoredRefreshToken {
+            expires_at: DateTime::from_millis(token.expires_at.timestamp_millis()),
+            token,
+            used: false,
+            revoked: false,
+        };
+        self.refresh_tokens.insert_one(stored, None).await?;
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: String,
+    ) -> Result<RefreshOutcome, Box<dyn std::error::Error + Send + Sync>> {
+        let filter = doc! {
+            "token.token_hash": token_hash.clone(),
+            "used": false,
+            "revoked": false,
+            "expires_at": { "$gt": DateTime::now() },
+        };
+        let update = doc! { "$set": { "used": true } };
+        if let Some(stored) = self
+            .refresh_tokens
+            .find_one_and_update(filter, update, None)
+            .await?
+        {
+            return Ok(RefreshOutcome::Valid(stored.token));
+        }
+
+        let filter = doc! { "token.token_hash": token_hash };
+        match self.refresh_tokens.find_one(filter, None).await? {
+            Some(stored) if stored.used || stored.revoked => {
+                Ok(RefreshOutcome::Reused(stored.token))
+            }
+            _ => Ok(RefreshOutcome::Invalid),
+        }
+    }
+
+    async fn revoke_family(
+        &self,
+        family_id: String,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.revoke_matching(doc! { "token.family_id": family_id })
+            .await
+    }
+
+    async fn revoke_user_sessions(
+        &self,
+        user_id: String,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.revoke_matching(doc! { "token.user_id": user_id })
+            .await
+    }
+
+    async fn revoke_access_token(
+        &self,
+        jti: String,
+        expires_at: chrono::DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Upsert so revoking the same token twice is harmless
+        let filter = doc! { "jti": jti.clone() };
+        let update = doc! {
+            "$setOnInsert": { "jti": jti, "expires_at": DateTime::from_millis(expires_at.timestamp_millis()) }
+        };
+        let options = mongodb::options::UpdateOptions::builder()
+            .upsert(true)
+            .build();
+        self.revoked_tokens
+            .update_one(filter, update, options)
+            .await?;
+        Ok(())
+    }
+
+    async fn is_revoked(
+        &self,
+        jti: String,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let filter = doc! { "jti": jti };
+        Ok(self.revoked_tokens.find_one(filter, None).await?.is_some())
+    }
+}
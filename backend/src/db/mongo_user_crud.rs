@@ -1,8 +1,14 @@
 use crate::db::{config::DbConfig, user_crud::UserRepository};
-use crate::models::user::{User, Votes};
+use crate::models::user::{PasskeyDetails, Role, User};
+use chrono::{DateTime, Utc};
 
 use mongodb::bson::{self, doc};
-use mongodb::{Client, Collection};
+use mongodb::{
+    error::{ErrorKind, WriteError, WriteFailure},
+    options::{IndexOptions, UpdateOptions},
+    {Client, Collection, IndexModel},
+};
+use webauthn_rs::prelude::Passkey;
 
 #[derive(Clone)]
 pub struct MongoUserRepo {
@@ -10,7 +16,9 @@ pub struct MongoUserRepo {
 }
 
 impl MongoUserRepo {
-    pub async fn new(config: &DbConfig) -> Self {
+    /// Fails when the unique user name index cannot be built, e.g. because existing users
+    /// share a name.
+    pub async fn new(config: &DbConfig) -> Result<Self, mongodb::error::Error> {
         // Create a MongoDB client
         let client = Client::with_uri_str(&config.connection_string)
             .await
@@ -18,27 +26,39 @@ impl MongoUserRepo {
 
         // Get the specified database and collection
         let database = client.database(&config.database_name);
-        let collection = database.collection("users");
+        let collection: Collection<User> = database.collection("users");
 
-        MongoUserRepo { collection }
+        // Usernames identify accounts at login, so they must be unique
+        let index = IndexModel::builder()
+            .keys(doc! { "user_name": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        collection.create_index(index, None).await?;
+
+        Ok(MongoUserRepo { collection })
     }
 }
 
+/// Whether `error` is a unique index violation.
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(WriteError { code: 11000, .. }))
+    )
+}
+
 #[async_trait::async_trait]
 impl UserRepository for MongoUserRepo {
-    async fn create_user(&self, user: User) -> Result<User, Box<dyn std::error::Error>> {
-        println!("Entered Create Poll Func");
-        println!("{:#?}", user);
+    async fn create_user(&self, user: User) -> Result<Option<User>, Box<dyn std::error::Error>> {
         match self.collection.insert_one(user.clone(), None).await {
-            Ok(insert_one_result) => {
-                println!("Added Poll Successfully {}", insert_one_result.inserted_id)
-            }
+            Ok(_) => Ok(Some(user)),
+            // Another sign-up claimed the name first; the unique index rejected this one
+            Err(e) if is_duplicate_key(&e) => Ok(None),
             Err(e) => {
-                eprintln!("Failed adding polls to db {}", e)
+                eprintln!("Failed adding user to db {}", e);
+                Err(Box::new(e))
             }
         }
-
-        Ok(user)
     }
 
     async fn get_user(
@@ -59,21 +79,147 @@ impl UserRepository for MongoUserRepo {
             }
         }
     }
-    async fn update_user(
+    async fn get_user_by_id(
         &self,
-        user_name: String,
-        vote: Votes,
+        user_id: String,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>> {
+        let filter = doc! { "user_id": user_id };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    async fn update_credentials(
+        &self,
+        user_id: String,
+        key: Passkey,
+        counter: u32,
+        last_used: DateTime<Utc>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let cred_id = key.cred_id().to_string();
+        // Authenticators without a counter always report 0, so only real counters are compared
+        let filter = if counter > 0 {
+            doc! {
+                "user_id": user_id.clone(),
+                "keys": { "$elemMatch": {
+                    "cred.cred_id": cred_id.clone(),
+                    "cred.counter": { "$lt": counter as i64 },
+                } },
+            }
+        } else {
+            doc! { "user_id": user_id.clone(), "keys.cred.cred_id": cred_id.clone() }
+        };
+        let update = doc! { "$set": { "keys.$[key]": bson::to_bson(&key)? } };
+        let options = UpdateOptions::builder()
+            .array_filters(vec![doc! { "key.cred.cred_id": cred_id.clone() }])
+            .build();
+        let result = self.collection.update_one(filter, update, options).await?;
+        if result.matched_count == 0 {
+            return Ok(false);
+        }
+
+        let filter = doc! { "user_id": user_id, "passkey_details.cred_id": cred_id };
+        let update = doc! { "$set": { "passkey_details.$.last_used": bson::to_bson(&last_used)? } };
+        self.collection.update_one(filter, update, None).await?;
+        Ok(true)
+    }
+
+    async fn add_passkey(
+        &self,
+        user_id: String,
+        key: Passkey,
+        details: PasskeyDetails,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let filter = doc! { "user_name": user_name };
+        let filter = doc! { "user_id": user_id };
         let update = doc! {
-            "$push": { "votes": bson::to_bson(&vote)? }
+            "$push": {
+                "keys": bson::to_bson(&key)?,
+                "passkey_details": bson::to_bson(&details)?,
+            }
         };
         self.collection.update_one(filter, update, None).await?;
         Ok(())
     }
-    async fn delete_user(&self, user_id: String) -> Result<(), Box<dyn std::error::Error>> {
+
+    async fn rename_passkey(
+        &self,
+        user_id: String,
+        cred_id: String,
+        nickname: String,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let filter =
+            doc! { "user_id": user_id.clone(), "passkey_details.cred_id": cred_id.clone() };
+        let update = doc! { "$set": { "passkey_details.$.nickname": nickname.clone() } };
+        let result = self.collection.update_one(filter, update, None).await?;
+        if result.matched_count > 0 {
+            return Ok(true);
+        }
+
+        // Keys enrolled before details were tracked get their details entry on first rename
+        let details = PasskeyDetails {
+            cred_id: cred_id.clone(),
+            nickname,
+            created_at: None,
+            last_used: None,
+        };
+        let filter = doc! { "user_id": user_id, "keys.cred.cred_id": cred_id };
+        let update = doc! { "$push": { "passkey_details": bson::to_bson(&details)? } };
+        let result = self.collection.update_one(filter, update, None).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn set_roles(
+        &self,
+        user_id: String,
+        roles: Vec<Role>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
         let filter = doc! { "user_id": user_id };
-        self.collection.delete_one(filter, None).await?;
-        Ok(())
+        let update = doc! { "$set": { "roles": bson::to_bson(&roles)? } };
+        let result = self.collection.update_one(filter, update, None).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn set_recovery_codes(
+        &self,
+        user_id: String,
+        code_hashes: Vec<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let filter = doc! { "user_id": user_id };
+        let update = doc! { "$set": { "recovery_codes": code_hashes } };
+        let result = self.collection.update_one(filter, update, None).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        user_name: String,
+        code_hash: String,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>> {
+        // Matching and pulling in one operation means a code can only ever be redeemed once
+        let filter = doc! { "user_name": user_name, "recovery_codes": code_hash.clone() };
+        let update = doc! { "$pull": { "recovery_codes": code_hash } };
+        Ok(self
+            .collection
+            .find_one_and_update(filter, update, None)
+            .await?)
+    }
+
+    async fn remove_passkey(
+        &self,
+        user_id: String,
+        cred_id: String,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // "keys.1" only exists while the user has at least two keys, so the last one is never pulled
+        let filter = doc! {
+            "user_id": user_id,
+            "keys.cred.cred_id": cred_id.clone(),
+            "keys.1": { "$exists": true },
+        };
+        let update = doc! {
+            "$pull": {
+                "keys": { "cred.cred_id": cred_id.clone() },
+                "passkey_details": { "cred_id": cred_id },
+            }
+        };
+        let result = self.collection.update_one(filter, update, None).await?;
+        Ok(result.modified_count > 0)
     }
 }
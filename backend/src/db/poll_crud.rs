@@ -1,22 +1,88 @@
-use crate::models::poll::Poll;
+use chrono::{DateTime, Utc};
+
+use crate::models::poll::{
+    Ballot, BallotChoice, CreatePollRequest, Poll, TransitionOutcome, UpdatePollRequest, Viewer,
+    VoteOutcome,
+};
 #[async_trait::async_trait]
 pub trait PollRepository: Send + Sync {
-    async fn create_poll(&self, poll: Poll) -> Result<Poll, Box<dyn std::error::Error>>;
-    async fn fetch_all(&self) -> Result<Vec<Poll>, Box<dyn std::error::Error>>;
+    /// Allocates a fresh poll ID and stores the validated request as a new poll by `creator`.
+    async fn create_poll(
+        &self,
+        request: CreatePollRequest,
+        creator: String,
+    ) -> Result<Poll, Box<dyn std::error::Error>>;
+    /// Every poll `viewer` may see; drafts and scheduled polls only reach their owner and admins.
+    async fn fetch_all(&self, viewer: Viewer) -> Result<Vec<Poll>, Box<dyn std::error::Error>>;
     async fn get_poll(
         &self,
         poll_id: i64,
     ) -> Result<Option<Poll>, Box<dyn std::error::Error + Send + Sync>>;
-    async fn update_poll(
+    /// The ballots stored for a poll that keeps them, in no particular order.
+    async fn ballots(
+        &self,
+        poll_id: i64,
+    ) -> Result<Vec<Ballot>, Box<dyn std::error::Error + Send + Sync>>;
+    /// Zeroes every option's count and discards every ballot, removing the poll from each
+    /// voter's history so they may vote again.
+    async fn reset_votes(&self, poll_id: i64) -> Result<(), Box<dyn std::error::Error>>;
+    /// Draft → scheduled, to open at `opens_at`.
+    async fn schedule_poll(
+        &self,
+        poll_id: i64,
+        opens_at: DateTime<Utc>,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>>;
+    /// Scheduled → active, opening the poll for votes now.
+    async fn activate_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>>;
+    /// Active → closed, ending the poll early.
+    async fn close_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>>;
+    /// Active → expired, moving the deadline to now.
+    async fn expire_poll(
         &self,
         poll_id: i64,
-        target: String,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>>;
+    /// Closed or expired → archived.
+    async fn archive_poll(
+        &self,
+        poll_id: i64,
+    ) -> Result<TransitionOutcome, Box<dyn std::error::Error>>;
     async fn delete_poll(&self, poll_id: i64) -> Result<(), Box<dyn std::error::Error>>;
+    /// Counts one ballot by `user_id` and records it in the user's voting history, both or
+    /// neither. A user gets one ballot per poll.
     async fn vote_poll(
         &self,
         poll_id: i64,
-        option_id: i64,
-        username: String,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+        choice: BallotChoice,
+        user_id: String,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>>;
+    /// Replaces the user's ballot with `choice`, keeping counts and history in step.
+    async fn change_vote(
+        &self,
+        poll_id: i64,
+        choice: BallotChoice,
+        user_id: String,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>>;
+    /// Withdraws the user's vote, after which they may vote again.
+    async fn retract_vote(
+        &self,
+        poll_id: i64,
+        user_id: String,
+    ) -> Result<VoteOutcome, Box<dyn std::error::Error>>;
+    /// Applies the given changes and returns the updated poll, or `None` if it does not exist
+    /// or its status no longer allows them.
+    async fn update_poll(
+        &self,
+        poll_id: i64,
+        update: UpdatePollRequest,
+    ) -> Result<Option<Poll>, Box<dyn std::error::Error>>;
+    /// Opens scheduled polls whose opening time is at or before `now`, returning how many.
+    async fn open_due(&self, now: DateTime<Utc>) -> Result<u64, Box<dyn std::error::Error>>;
+    /// Marks active polls whose deadline is at or before `now` as expired, returning how many.
+    async fn expire_due(&self, now: DateTime<Utc>) -> Result<u64, Box<dyn std::error::Error>>;
 }
@@ -0,0 +1,56 @@
+use crate::models::social_recovery::{RecoverySetup, SocialRecoveryRequest, TrusteeShare};
+
+#[async_trait::async_trait]
+pub trait SocialRecoveryRepository: Send + Sync {
+    /// Replaces the owner's setup and queues `shares` for the trustees to collect, dropping any
+    /// recovery already in progress and any shares of the old secret not yet collected.
+    async fn set_trustees(
+        &self,
+        setup: RecoverySetup,
+        shares: Vec<TrusteeShare>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    async fn get_setup(
+        &self,
+        owner_id: String,
+    ) -> Result<Option<RecoverySetup>, Box<dyn std::error::Error + Send + Sync>>;
+    /// Removes the owner's setup, uncollected shares and pending recoveries. Returns false if there was
+    /// no setup.
+    async fn remove_trustees(
+        &self,
+        owner_id: String,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+    /// Hands over the trustee's uncollected shares, removing them from storage.
+    async fn take_shares(
+        &self,
+        trustee_id: String,
+    ) -> Result<Vec<TrusteeShare>, Box<dyn std::error::Error + Send + Sync>>;
+    async fn create_request(
+        &self,
+        request: SocialRecoveryRequest,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    /// Live requests from owners who named `trustee_id` as a trustee.
+    async fn pending_requests_for_trustee(
+        &self,
+        trustee_id: String,
+    ) -> Result<Vec<SocialRecoveryRequest>, Box<dyn std::error::Error + Send + Sync>>;
+    /// Adds the share a trustee submitted to a live request. Returns false when the trustee is
+    /// not one of the owner's trustees, has already approved, or the request is gone.
+    async fn approve_request(
+        &self,
+        request_id: String,
+        trustee_id: String,
+        share: String,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+    /// Looks up a live request, provided `request_secret_hash` matches.
+    async fn get_request(
+        &self,
+        request_id: String,
+        request_secret_hash: String,
+    ) -> Result<Option<SocialRecoveryRequest>, Box<dyn std::error::Error + Send + Sync>>;
+    /// Like `get_request`, but also deletes it so that it can only be completed once.
+    async fn take_request(
+        &self,
+        request_id: String,
+        request_secret_hash: String,
+    ) -> Result<Option<SocialRecoveryRequest>, Box<dyn std::error::Error + Send + Sync>>;
+}
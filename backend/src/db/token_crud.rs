@@ -0,0 +1,34 @@
+use crate::models::token::{RefreshOutcome, RefreshToken};
+use chrono::{DateTime, Utc};
+
+#[async_trait::async_trait]
+pub trait TokenRepository: Send + Sync {
+    async fn store_refresh_token(
+        &self,
+        token: RefreshToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    /// Atomically marks a live token as used, so each refresh token rotates exactly once.
+    async fn consume_refresh_token(
+        &self,
+        token_hash: String,
+    ) -> Result<RefreshOutcome, Box<dyn std::error::Error + Send + Sync>>;
+    /// Revokes every refresh token in the family and the access tokens issued with them.
+    async fn revoke_family(
+        &self,
+        family_id: String,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    /// Revokes every session of the user.
+    async fn revoke_user_sessions(
+        &self,
+        user_id: String,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    async fn revoke_access_token(
+        &self,
+        jti: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+    async fn is_revoked(
+        &self,
+        jti: String,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+}
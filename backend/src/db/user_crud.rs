@@ -1,14 +1,64 @@
-use crate::models::user::{User, Votes};
+use crate::models::user::{PasskeyDetails, Role, User};
+use chrono::{DateTime, Utc};
+use webauthn_rs::prelude::Passkey;
 
 #[async_trait::async_trait]
 pub trait UserRepository: Send + Sync {
-    async fn create_user(&self, user: User) -> Result<User, Box<dyn std::error::Error>>;
+    /// Returns None when another user already has this name.
+    async fn create_user(&self, user: User) -> Result<Option<User>, Box<dyn std::error::Error>>;
     async fn get_user(&self, user_name: String)
         -> Result<Option<User>, Box<dyn std::error::Error>>;
-    async fn update_user(
+    async fn get_user_by_id(
         &self,
-        user_name: String,
-        vote: Votes,
+        user_id: String,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>>;
+    /// Stores `key` in place of the user's key with the same credential ID after a login.
+    /// A non-zero `counter` must be above the stored one, otherwise nothing is written and
+    /// false is returned.
+    async fn update_credentials(
+        &self,
+        user_id: String,
+        key: Passkey,
+        counter: u32,
+        last_used: DateTime<Utc>,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+    async fn add_passkey(
+        &self,
+        user_id: String,
+        key: Passkey,
+        details: PasskeyDetails,
     ) -> Result<(), Box<dyn std::error::Error>>;
-    async fn delete_user(&self, user_id: String) -> Result<(), Box<dyn std::error::Error>>;
+    /// Returns false when the user has no key with this credential ID.
+    async fn rename_passkey(
+        &self,
+        user_id: String,
+        cred_id: String,
+        nickname: String,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+    /// Returns false when the user does not exist.
+    async fn set_roles(
+        &self,
+        user_id: String,
+        roles: Vec<Role>,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+    /// Replaces the user's recovery code hashes, invalidating the previous set. Returns false
+    /// when the user does not exist.
+    async fn set_recovery_codes(
+        &self,
+        user_id: String,
+        code_hashes: Vec<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+    /// Atomically removes `code_hash` from the user's recovery codes, returning the user only
+    /// if the code was still unused.
+    async fn consume_recovery_code(
+        &self,
+        user_name: String,
+        code_hash: String,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>>;
+    /// Returns false when the key does not exist or is the user's last one.
+    async fn remove_passkey(
+        &self,
+        user_id: String,
+        cred_id: String,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
 }
@@ -0,0 +1,49 @@
+use crate::db::{audit_crud::AuditRepository, user_crud::UserRepository};
+use crate::models::audit::{AuditEvent, AuditKind};
+use crate::models::jwt::AdminClaims;
+use crate::models::user::Role;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{put, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RolesRequest {
+    roles: Vec<Role>,
+}
+
+/// Replaces a user's roles. They take effect when the user's access token is next refreshed.
+#[put("users/{user_id}/roles")]
+pub async fn set_user_roles(
+    admin: AdminClaims,
+    path: Path<String>,
+    request: Json<RolesRequest>,
+    db: Data<dyn UserRepository>,
+    audit: Data<dyn AuditRepository>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+    let mut roles = request.into_inner().roles;
+    // Every account stays a member whatever else it is granted
+    if !roles.contains(&Role::Member) {
+        roles.push(Role::Member);
+    }
+    roles.sort();
+    roles.dedup();
+
+    match db.set_roles(user_id.clone(), roles.clone()).await {
+        Ok(true) => {
+            let event = AuditEvent::new(
+                AuditKind::RolesChanged,
+                Some(user_id),
+                format!("Roles set to {:?} by {}", roles, admin.0.uuid),
+            );
+            if let Err(e) = audit.record(event).await {
+                eprintln!("Failed to record audit event: {}", e);
+            }
+            HttpResponse::Ok().json(json!({ "roles": roles }))
+        }
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "User not found" })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error updating roles: {}", err) })),
+    }
+}
@@ -1,86 +1,153 @@
-use crate::db::user_crud::UserRepository;
+use crate::config::AppConfig;
+use crate::db::{
+    audit_crud::AuditRepository, ceremony_crud::CeremonyStateRepository,
+    token_crud::TokenRepository, user_crud::UserRepository,
+};
+use crate::handler::session::issue_tokens;
 use crate::handler::{Error, WebResult};
-use crate::models::jwt::encode_jwt;
-use crate::models::{auth_state::AuthenticationState, reg_state::RegistrationState, user::User};
-use actix_web::error::ErrorInternalServerError;
+use crate::models::audit::{AuditEvent, AuditKind};
+use crate::models::ceremony::{
+    AuthenticationCeremony, CeremonyQuery, CeremonyResponse, DiscoverableCeremony,
+    RegistrationCeremony,
+};
+use crate::models::jwt::JwtKeys;
+use crate::models::recovery::RecoveryCodes;
+use crate::models::user::{PasskeyDetails, Role, User};
 use actix_web::post;
-use actix_web::web::{Data, Json, Path};
+use actix_web::web::{Data, Json, Path, Query};
 use actix_web::HttpResponse;
 use log::info;
 use serde_json::json;
 use webauthn_rs::prelude::*;
 
+/// Roles for a new account: everyone is a member, and usernames listed in
+/// `accounts.admin_users` are also admins.
+fn initial_roles(config: &AppConfig, username: &str) -> Vec<Role> {
+    if config
+        .accounts
+        .admin_users
+        .iter()
+        .any(|admin| admin == username)
+    {
+        vec![Role::Admin, Role::Member]
+    } else {
+        vec![Role::Member]
+    }
+}
+
+async fn ensure_username_available(db: &dyn UserRepository, username: &str) -> WebResult<()> {
+    match db.get_user(username.to_string()).await {
+        Ok(None) => Ok(()),
+        Ok(Some(_)) => Err(Error::UsernameTaken),
+        Err(err) => Err(Error::Storage(err.to_string().into())),
+    }
+}
+
 #[post("start_reg/{username}")]
 pub(crate) async fn start_register(
     username: Path<String>,
-    reg_state_storage: Data<RegistrationState>,
+    db: Data<dyn UserRepository>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
     webauthn: Data<Webauthn>,
-) -> WebResult<Json<CreationChallengeResponse>> {
+) -> WebResult<Json<CeremonyResponse<CreationChallengeResponse>>> {
     info!("Start register");
-    reg_state_storage.remove("reg_state".to_string()).await;
 
     let username = username.into_inner();
+    ensure_username_available(db.get_ref(), &username).await?;
     let user_unique_id = Uuid::new_v4();
-    let exclude_credentials = { None };
-    println!("exclude creds : {:#?}", exclude_credentials);
 
-    let (ccr, reg_state) = webauthn
-        .start_passkey_registration(user_unique_id, &username, &username, exclude_credentials)
+    let (mut ccr, reg_state) = webauthn
+        .start_passkey_registration(user_unique_id, &username, &username, None)
         .map_err(|e| {
             info!("challenge_register -> {:?}", e);
             Error::Unknown(e)
         })?;
+    require_discoverable(&mut ccr);
     info!(
         "Inserting reg_state into session: {:?}",
         (username.clone(), user_unique_id, reg_state.clone())
     );
 
-    reg_state_storage
-        .insert((username.to_string(), user_unique_id, reg_state.clone()))
-        .await;
+    let ceremony_id = ceremony_store
+        .insert_registration(RegistrationCeremony {
+            user_name: username.to_string(),
+            user_id: user_unique_id,
+            state: reg_state.clone(),
+            existing_account: false,
+            nickname: None,
+        })
+        .await
+        .map_err(Error::Storage)?;
 
-    println!("{:#?}", reg_state);
     info!("Registration Successful!");
-    Ok(Json(ccr))
+    Ok(Json(CeremonyResponse {
+        ceremony_id,
+        challenge: ccr,
+    }))
 }
 
 #[post("finish_reg")]
 pub(crate) async fn finish_register(
     req: Json<RegisterPublicKeyCredential>,
-    reg_state_storage: Data<RegistrationState>,
+    query: Query<CeremonyQuery>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
     db: Data<dyn UserRepository>,
     webauthn: Data<Webauthn>,
-) -> HttpResponse {
-    println!("Entered finsih reg");
-    let registration_state = reg_state_storage
-        .get("reg_state".to_string())
+    config: Data<AppConfig>,
+) -> WebResult<HttpResponse> {
+    let registration_state = ceremony_store
+        .take_registration(query.ceremony_id)
         .await
+        .map_err(Error::Storage)?
         .ok_or_else(|| {
-            eprintln!("Registration state not found for user ID:");
+            eprintln!(
+                "Registration state not found for ceremony: {}",
+                query.ceremony_id
+            );
             Error::CorruptSession
-        })
-        .unwrap();
-    let (username, user_unique_id, reg_state) = registration_state;
+        })?;
+    let RegistrationCeremony {
+        user_name: username,
+        user_id: user_unique_id,
+        state: reg_state,
+        existing_account,
+        nickname,
+    } = registration_state;
+    if existing_account {
+        // Keys for existing accounts are finished through the passkeys endpoints
+        return Err(Error::CorruptSession);
+    }
+    // Someone may have claimed the name while this ceremony was in flight
+    ensure_username_available(db.get_ref(), &username).await?;
 
-    let sk = webauthn
+    let final_keys = webauthn
         .finish_passkey_registration(&req, &reg_state)
         .map_err(|e| {
             info!("challenge_register -> {:?}", e);
             Error::BadRequest(e)
-        });
-    let final_keys = sk.unwrap();
+        })?;
 
+    let details = PasskeyDetails::new(
+        &final_keys,
+        nickname.unwrap_or_else(|| "Passkey".to_string()),
+    );
+    // Shown once here; only the hashes are kept
+    let recovery = RecoveryCodes::generate();
     let user = User {
         user_id: user_unique_id.to_string(),
+        roles: initial_roles(&config, &username),
+        recovery_codes: recovery.hashes,
         user_name: username,
         keys: vec![final_keys],
+        passkey_details: vec![details],
         owned_polls: None,
         polls_voted: None,
     };
 
     match db.create_user(user).await {
-        Ok(_) => HttpResponse::Ok().body("success"),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Ok(Some(_)) => Ok(HttpResponse::Ok().json(json!({ "recovery_codes": recovery.codes }))),
+        Ok(None) => Err(Error::UsernameTaken),
+        Err(err) => Err(Error::Storage(err.to_string().into())),
     }
 }
 
@@ -88,11 +155,10 @@ pub(crate) async fn finish_register(
 pub(crate) async fn start_authentication(
     username: Path<String>,
     db: Data<dyn UserRepository>,
-    auth_state_store: Data<AuthenticationState>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
     webauthn: Data<Webauthn>,
 ) -> HttpResponse {
     info!("Start Authentication");
-    auth_state_store.remove("auth_state".to_string()).await;
 
     match db.get_user(username.to_string()).await {
         Ok(user_doc) => {
@@ -102,12 +168,24 @@ pub(crate) async fn start_authentication(
                         let allow_credentials = user.keys;
                         match webauthn.start_passkey_authentication(&allow_credentials) {
                             Ok((rcr, auth_state)) => {
-                                auth_state_store
-                                    .insert((user_unique_id, auth_state.clone()))
-                                    .await;
-
-                                // Return a successful response with the JSON payload
-                                HttpResponse::Ok().json(rcr)
+                                let ceremony = AuthenticationCeremony {
+                                    user_id: user_unique_id,
+                                    state: auth_state,
+                                };
+                                match ceremony_store.insert_authentication(ceremony).await {
+                                    // Return a successful response with the JSON payload
+                                    Ok(ceremony_id) => HttpResponse::Ok().json(CeremonyResponse {
+                                        ceremony_id,
+                                        challenge: rcr,
+                                    }),
+                                    Err(e) => {
+                                        info!("Failed to store authentication state: {}", e);
+                                        HttpResponse::InternalServerError().json(format!(
+                                            "Failed to store authentication state: {}",
+                                            e
+                                        ))
+                                    }
+                                }
                             }
                             Err(e) => {
                                 // Log and return an error response if authentication fails
@@ -137,34 +215,44 @@ pub(crate) async fn start_authentication(
     }
 }
 #[post("/finish_auth/{username}")]
+#[allow(clippy::too_many_arguments)] // one extractor per dependency, as actix expects
 pub(crate) async fn finish_authentication(
     auth: Json<PublicKeyCredential>,
     username: Path<String>,
-    auth_state_store: Data<AuthenticationState>,
+    query: Query<CeremonyQuery>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
     db: Data<dyn UserRepository>,
+    audit: Data<dyn AuditRepository>,
+    tokens: Data<dyn TokenRepository>,
+    jwt_keys: Data<JwtKeys>,
     webauthn: Data<Webauthn>,
 ) -> WebResult<HttpResponse> {
-    println!("Entered finish auth");
-
     // Retrieve the authentication state
-    let auth_state = auth_state_store
-        .get("auth_state".to_string())
+    let auth_state = ceremony_store
+        .take_authentication(query.ceremony_id)
         .await
+        .map_err(Error::Storage)?
         .ok_or_else(|| {
-            eprintln!("Registration state not found for user ID");
+            eprintln!(
+                "Authentication state not found for ceremony: {}",
+                query.ceremony_id
+            );
             Error::CorruptSession
         })?;
 
     let username = username.into_inner();
-    let (user_unique_id, auth_state) = auth_state;
+    let AuthenticationCeremony {
+        user_id: user_unique_id,
+        state: auth_state,
+    } = auth_state;
 
     // Authenticate using the passkey
-    let auth_result = webauthn
-        .finish_passkey_authentication(&auth, &auth_state)
-        .map_err(|e| {
-            info!("challenge_register -> {:?}", e);
-            Error::BadRequest(e)
-        })?;
+    let auth_result = match webauthn.finish_passkey_authentication(&auth, &auth_state) {
+        Ok(auth_result) => auth_result,
+        Err(e) => {
+            return Err(authentication_error(audit.get_ref(), &user_unique_id, &auth, e).await)
+        }
+    };
 
     // Fetch the user from the database
     let user_doc = db.get_user(username.clone()).await;
@@ -177,32 +265,17 @@ pub(crate) async fn finish_authentication(
         return Ok(HttpResponse::InternalServerError().json(resp_body));
     }
 
-    if let Some(mut user) = user_doc.unwrap() {
-        // Update the credentials for the user
-        user.keys.iter_mut().for_each(|key| {
-            key.update_credential(&auth_result);
-        });
-        // temporary workaround
-        db.delete_user(user_unique_id.to_string()).await.unwrap();
-        db.create_user(user).await.unwrap();
-
-        info!("Authentication Successful!");
-
-        // Generate JWT token
-        let token =
-            encode_jwt(&user_unique_id).map_err(|err| ErrorInternalServerError(err.to_string()));
-
-        let resp_body = match token {
-            Ok(token) => json!({
-                "token": token,
-            }),
-            Err(e) => json!({
-                "error": format!("Error generating token: {}", e),
-            }),
-        };
-
-        println!("{:#?}", resp_body);
-        Ok(HttpResponse::Ok().json(resp_body))
+    if let Some(user) = user_doc.unwrap() {
+        complete_authentication(
+            db.get_ref(),
+            audit.get_ref(),
+            tokens.get_ref(),
+            &jwt_keys,
+            user,
+            user_unique_id,
+            &auth_result,
+        )
+        .await
     } else {
         // User not found in DB
         let resp_body = json!({
@@ -211,3 +284,183 @@ pub(crate) async fn finish_authentication(
         Ok(HttpResponse::NotFound().json(resp_body))
     }
 }
+
+/// Persists the updated credential state and issues a token once a passkey assertion checks out.
+async fn complete_authentication(
+    db: &dyn UserRepository,
+    audit: &dyn AuditRepository,
+    tokens: &dyn TokenRepository,
+    keys: &JwtKeys,
+    mut user: User,
+    user_unique_id: Uuid,
+    auth_result: &AuthenticationResult,
+) -> WebResult<HttpResponse> {
+    // Update the credential that was used, leaving the others untouched
+    let key = user
+        .keys
+        .iter_mut()
+        .find(|key| key.cred_id() == auth_result.cred_id())
+        .ok_or(Error::CorruptSession)?;
+    key.update_credential(auth_result);
+
+    let stored = db
+        .update_credentials(
+            user_unique_id.to_string(),
+            key.clone(),
+            auth_result.counter(),
+            chrono::Utc::now(),
+        )
+        .await
+        .map_err(|err| Error::Storage(err.to_string().into()))?;
+    if !stored {
+        // Another login already moved the counter past this one
+        return Err(reject_possible_clone(audit, &user_unique_id, auth_result.cred_id()).await);
+    }
+
+    info!("Authentication Successful!");
+
+    // Every login starts a new refresh token family
+    let tokens = issue_tokens(
+        tokens,
+        keys,
+        &user_unique_id,
+        user.roles.clone(),
+        Uuid::new_v4(),
+    )
+    .await
+    .map_err(Error::Storage)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": tokens.access_token,
+        "refresh_token": tokens.refresh_token,
+        "user_id": user_unique_id,
+        "user_name": user.user_name,
+    })))
+}
+
+/// Records a signature counter regression and builds the error returned to the client.
+async fn reject_possible_clone(
+    audit: &dyn AuditRepository,
+    user_unique_id: &Uuid,
+    cred_id: &CredentialID,
+) -> Error {
+    let event = AuditEvent::new(
+        AuditKind::CounterRegression,
+        Some(user_unique_id.to_string()),
+        format!(
+            "Signature counter did not increase for credential {}",
+            cred_id
+        ),
+    );
+    if let Err(e) = audit.record(event).await {
+        eprintln!("Failed to record audit event: {}", e);
+    }
+    Error::PossibleClonedAuthenticator
+}
+
+/// Maps a failed assertion to an error, auditing the ones that point at a cloned authenticator.
+async fn authentication_error(
+    audit: &dyn AuditRepository,
+    user_unique_id: &Uuid,
+    auth: &PublicKeyCredential,
+    e: WebauthnError,
+) -> Error {
+    info!("challenge_authenticate -> {:?}", e);
+    match e {
+        WebauthnError::CredentialPossibleCompromise => {
+            let cred_id = CredentialID::from(auth.get_credential_id().to_vec());
+            reject_possible_clone(audit, user_unique_id, &cred_id).await
+        }
+        e => Error::BadRequest(e),
+    }
+}
+
+/// Asks the authenticator to store the credential on the device, so the account can be picked
+/// from the browser without typing a username.
+pub(crate) fn require_discoverable(ccr: &mut CreationChallengeResponse) {
+    if let Some(selection) = ccr.public_key.authenticator_selection.as_mut() {
+        selection.require_resident_key = true;
+    }
+}
+
+#[post("start_discoverable_auth")]
+pub(crate) async fn start_discoverable_authentication(
+    ceremony_store: Data<dyn CeremonyStateRepository>,
+    webauthn: Data<Webauthn>,
+) -> WebResult<Json<CeremonyResponse<RequestChallengeResponse>>> {
+    info!("Start discoverable authentication");
+
+    let (rcr, state) = webauthn.start_discoverable_authentication().map_err(|e| {
+        info!("challenge_authenticate -> {:?}", e);
+        Error::Unknown(e)
+    })?;
+
+    let ceremony_id = ceremony_store
+        .insert_discoverable(DiscoverableCeremony { state })
+        .await
+        .map_err(Error::Storage)?;
+
+    Ok(Json(CeremonyResponse {
+        ceremony_id,
+        challenge: rcr,
+    }))
+}
+
+#[post("finish_discoverable_auth")]
+#[allow(clippy::too_many_arguments)] // one extractor per dependency, as actix expects
+pub(crate) async fn finish_discoverable_authentication(
+    auth: Json<PublicKeyCredential>,
+    query: Query<CeremonyQuery>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
+    db: Data<dyn UserRepository>,
+    audit: Data<dyn AuditRepository>,
+    tokens: Data<dyn TokenRepository>,
+    jwt_keys: Data<JwtKeys>,
+    webauthn: Data<Webauthn>,
+) -> WebResult<HttpResponse> {
+    let ceremony = ceremony_store
+        .take_discoverable(query.ceremony_id)
+        .await
+        .map_err(Error::Storage)?
+        .ok_or_else(|| {
+            eprintln!(
+                "Authentication state not found for ceremony: {}",
+                query.ceremony_id
+            );
+            Error::CorruptSession
+        })?;
+
+    // The user handle chosen by the browser is the UUID stored in `User.user_id`
+    let (user_unique_id, _) = webauthn
+        .identify_discoverable_authentication(&auth)
+        .map_err(Error::BadRequest)?;
+
+    let user = match db.get_user_by_id(user_unique_id.to_string()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(HttpResponse::NotFound().json(json!({ "error": "User not found" }))),
+        Err(err) => {
+            return Ok(HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching user: {}", err) })))
+        }
+    };
+
+    let creds: Vec<DiscoverableKey> = user.keys.iter().map(DiscoverableKey::from).collect();
+    let auth_result =
+        match webauthn.finish_discoverable_authentication(&auth, ceremony.state, &creds) {
+            Ok(auth_result) => auth_result,
+            Err(e) => {
+                return Err(authentication_error(audit.get_ref(), &user_unique_id, &auth, e).await)
+            }
+        };
+
+    complete_authentication(
+        db.get_ref(),
+        audit.get_ref(),
+        tokens.get_ref(),
+        &jwt_keys,
+        user,
+        user_unique_id,
+        &auth_result,
+    )
+    .await
+}
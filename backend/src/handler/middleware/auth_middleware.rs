@@ -1,17 +1,36 @@
 use actix_web::error::ErrorUnauthorized;
 use actix_web::{
     body::MessageBody,
-    dev::{Service, ServiceRequest, ServiceResponse, Transform},
-    http::header::AUTHORIZATION,
+    dev::{ResourceDef, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header::AUTHORIZATION, Method},
+    web::Data,
     Error, HttpMessage,
 };
 use futures::future::{ok, LocalBoxFuture, Ready};
 use std::rc::Rc;
 
-use crate::models::jwt::decode_jwt;
+use crate::db::token_crud::TokenRepository;
+use crate::models::jwt::{decode_jwt, JwtKeys};
 
 // Middleware struct
-pub struct CheckAuth;
+#[derive(Default, Clone)]
+pub struct CheckAuth {
+    // Routes that may be called without a token, as (method, full path pattern)
+    public: Rc<Vec<(Method, ResourceDef)>>,
+}
+
+impl CheckAuth {
+    pub fn new() -> Self {
+        CheckAuth::default()
+    }
+
+    /// Lets `method` requests matching `path` (e.g. "/api/polls/{poll_id}") through without a
+    /// token. Claims are still attached when a valid token is sent.
+    pub fn allow_public(mut self, method: Method, path: &str) -> Self {
+        Rc::make_mut(&mut self.public).push((method, ResourceDef::new(path)));
+        self
+    }
+}
 
 // Implement `Transform` trait for `CheckAuth`
 impl<S, B> Transform<S, ServiceRequest> for CheckAuth
@@ -29,6 +48,7 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ok(CheckAuthMiddleware {
             service: Rc::new(service),
+            public: Rc::clone(&self.public),
         })
     }
 }
@@ -36,6 +56,7 @@ where
 // Inner middleware struct to hold the service
 pub struct CheckAuthMiddleware<S> {
     service: Rc<S>,
+    public: Rc<Vec<(Method, ResourceDef)>>,
 }
 
 // Implement `Service` trait for `CheckAuthMiddleware`
@@ -59,17 +80,24 @@ where
     fn call(&self, req: ServiceRequest) -> Self::Future {
         // Get the Authorization header
         let auth_header = req.headers().get(AUTHORIZATION).cloned();
+        let is_public = self
+            .public
+            .iter()
+            .any(|(method, path)| req.method() == method && path.is_match(req.path()));
+        let token_repo = req.app_data::<Data<dyn TokenRepository>>().cloned();
+        let jwt_keys = req.app_data::<Data<JwtKeys>>().cloned();
 
         // Clone the service to avoid moving it into the async block
         let service = Rc::clone(&self.service);
 
         Box::pin(async move {
-            if let Some(auth) = auth_header {
+            if let (Some(auth), Some(token_repo), Some(jwt_keys)) =
+                (auth_header, token_repo, jwt_keys)
+            {
                 if let Ok(auth_str) = auth.to_str() {
                     let token = auth_str.replace("Bearer ", "");
                     // Attempt to decode the JWT token
-                    if let Ok(claim) = decode_jwt(token) {
-                        println!("after decoding : {:#?}", claim);
+                    if let Ok(claim) = decode_jwt(token, &jwt_keys, token_repo.get_ref()).await {
                         // Insert the claims into request extensions
                         req.extensions_mut().insert(claim.claims);
 
@@ -79,6 +107,10 @@ where
                 }
             }
 
+            if is_public {
+                return service.call(req).await;
+            }
+
             // Return Unauthorized response if auth is missing or invalid
             Err(ErrorUnauthorized("Unauthorized"))
         })
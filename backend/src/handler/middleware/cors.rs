@@ -0,0 +1,88 @@
+use actix_cors::Cors;
+use actix_web::{
+    dev::ResourceDef,
+    guard::{Guard, GuardContext},
+    http::{
+        header::{ACCEPT, ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, CONTENT_TYPE},
+        Method,
+    },
+};
+
+use crate::config::CorsConfig;
+
+/// Builds the CORS policy for each group of routes from the configured origins.
+#[derive(Clone)]
+pub struct CorsPolicies {
+    config: CorsConfig,
+}
+
+impl CorsPolicies {
+    pub fn new(config: CorsConfig) -> Self {
+        CorsPolicies { config }
+    }
+
+    /// For auth and mutation endpoints: only the frontend origins, credentials as configured.
+    pub fn strict(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allowed_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT]);
+        for origin in &self.config.allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
+        if self.config.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors.max_age(self.config.max_age_secs)
+    }
+
+    /// For public read endpoints: GET only and never with credentials, so it may be opened up to
+    /// `public_origins`, or to every origin when that list is empty.
+    pub fn public_read(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods([Method::GET])
+            .allowed_headers([AUTHORIZATION, ACCEPT]);
+        if self.config.public_origins.is_empty() {
+            cors = cors.allow_any_origin().send_wildcard();
+        } else {
+            for origin in &self.config.public_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
+        cors.max_age(self.config.max_age_secs)
+    }
+}
+
+/// Matches GET requests for `paths` and their CORS preflights, so a scope guarded by it can
+/// answer those with the public read policy and leave everything else to the next scope.
+pub struct PublicRead {
+    paths: Vec<ResourceDef>,
+}
+
+impl PublicRead {
+    pub fn new(paths: &[&str]) -> Self {
+        PublicRead {
+            paths: paths.iter().map(|path| ResourceDef::new(*path)).collect(),
+        }
+    }
+}
+
+impl Guard for PublicRead {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        let head = ctx.head();
+        let is_read = match head.method {
+            Method::GET => true,
+            Method::OPTIONS => head
+                .headers()
+                .get(ACCESS_CONTROL_REQUEST_METHOD)
+                .is_some_and(|method| method == "GET"),
+            _ => false,
+        };
+        is_read && self.paths.iter().any(|path| path.is_match(head.uri.path()))
+    }
+}
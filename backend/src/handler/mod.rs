@@ -2,9 +2,14 @@ use actix_web::http::StatusCode;
 use thiserror::Error;
 use webauthn_rs::prelude::WebauthnError;
 
+pub mod admin;
 pub(crate) mod auth;
 pub mod middleware;
+pub mod passkey;
 pub mod poll;
+pub mod recovery;
+pub mod session;
+pub mod social_recovery;
 /**
 Type alias for Errors that implement [actix_web::ResponseError] through [Error]
 */
@@ -21,10 +26,20 @@ pub(crate) enum Error {
     CorruptSession,
     #[error("Bad request")]
     BadRequest(#[from] WebauthnError),
+    #[error("Storage error: {0}")]
+    Storage(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Username already taken")]
+    UsernameTaken,
+    #[error("Signature counter went backwards; this authenticator may have been cloned")]
+    PossibleClonedAuthenticator,
 }
 
 impl actix_web::ResponseError for Error {
     fn status_code(&self) -> StatusCode {
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            Error::UsernameTaken => StatusCode::CONFLICT,
+            Error::PossibleClonedAuthenticator => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 }
@@ -0,0 +1,187 @@
+use crate::db::{
+    ceremony_crud::CeremonyStateRepository, token_crud::TokenRepository, user_crud::UserRepository,
+};
+use crate::handler::{auth::require_discoverable, Error, WebResult};
+use crate::models::ceremony::{CeremonyQuery, CeremonyResponse, RegistrationCeremony};
+use crate::models::jwt::{Claims, EnrollmentClaims, TokenScope};
+use crate::models::user::PasskeyDetails;
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, post, put, HttpResponse};
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use webauthn_rs::prelude::*;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NicknameRequest {
+    nickname: String,
+}
+
+#[post("start")]
+pub async fn start_add_passkey(
+    EnrollmentClaims(claims): EnrollmentClaims,
+    request: Json<NicknameRequest>,
+    db: Data<dyn UserRepository>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
+    webauthn: Data<Webauthn>,
+) -> WebResult<HttpResponse> {
+    info!("Start adding passkey");
+    let user = match db.get_user_by_id(claims.uuid.to_string()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(HttpResponse::NotFound().json(json!({ "error": "User not found" }))),
+        Err(err) => {
+            return Ok(HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching user: {}", err) })))
+        }
+    };
+
+    // Stop the same authenticator from being enrolled twice
+    let exclude_credentials = user.keys.iter().map(|key| key.cred_id().clone()).collect();
+
+    let (mut ccr, reg_state) = webauthn
+        .start_passkey_registration(
+            claims.uuid,
+            &user.user_name,
+            &user.user_name,
+            Some(exclude_credentials),
+        )
+        .map_err(|e| {
+            info!("challenge_register -> {:?}", e);
+            Error::Unknown(e)
+        })?;
+    require_discoverable(&mut ccr);
+
+    let ceremony_id = ceremony_store
+        .insert_registration(RegistrationCeremony {
+            user_name: user.user_name,
+            user_id: claims.uuid,
+            state: reg_state,
+            existing_account: true,
+            nickname: Some(request.into_inner().nickname),
+        })
+        .await
+        .map_err(Error::Storage)?;
+
+    Ok(HttpResponse::Ok().json(CeremonyResponse {
+        ceremony_id,
+        challenge: ccr,
+    }))
+}
+
+#[post("finish")]
+pub async fn finish_add_passkey(
+    EnrollmentClaims(claims): EnrollmentClaims,
+    req: Json<RegisterPublicKeyCredential>,
+    query: Query<CeremonyQuery>,
+    db: Data<dyn UserRepository>,
+    tokens: Data<dyn TokenRepository>,
+    ceremony_store: Data<dyn CeremonyStateRepository>,
+    webauthn: Data<Webauthn>,
+) -> WebResult<HttpResponse> {
+    let ceremony = ceremony_store
+        .take_registration(query.ceremony_id)
+        .await
+        .map_err(Error::Storage)?
+        .ok_or_else(|| {
+            eprintln!(
+                "Registration state not found for ceremony: {}",
+                query.ceremony_id
+            );
+            Error::CorruptSession
+        })?;
+    // The ceremony must have been started by this user through `start_add_passkey`
+    if !ceremony.existing_account || ceremony.user_id != claims.uuid {
+        return Err(Error::CorruptSession);
+    }
+
+    let key = webauthn
+        .finish_passkey_registration(&req, &ceremony.state)
+        .map_err(|e| {
+            info!("challenge_register -> {:?}", e);
+            Error::BadRequest(e)
+        })?;
+    let details = PasskeyDetails::new(
+        &key,
+        ceremony.nickname.unwrap_or_else(|| "Passkey".to_string()),
+    );
+
+    match db
+        .add_passkey(claims.uuid.to_string(), key, details.clone())
+        .await
+    {
+        Ok(_) => {
+            if claims.scope == TokenScope::Recovery {
+                // A recovery token is good for exactly one enrollment
+                let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0)
+                    .unwrap_or_else(chrono::Utc::now);
+                if let Err(e) = tokens
+                    .revoke_access_token(claims.jti.to_string(), expires_at)
+                    .await
+                {
+                    eprintln!("Failed to revoke recovery token: {}", e);
+                }
+            }
+            Ok(HttpResponse::Ok().json(details))
+        }
+        Err(err) => Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Error adding passkey: {}", err),
+        }))),
+    }
+}
+
+#[get("")]
+pub async fn list_passkeys(claims: Claims, db: Data<dyn UserRepository>) -> HttpResponse {
+    match db.get_user_by_id(claims.uuid.to_string()).await {
+        Ok(Some(user)) => HttpResponse::Ok().json(user.list_passkeys()),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "User not found" })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error fetching user: {}", err) })),
+    }
+}
+
+#[put("{cred_id}")]
+pub async fn rename_passkey(
+    claims: Claims,
+    path: Path<String>,
+    request: Json<NicknameRequest>,
+    db: Data<dyn UserRepository>,
+) -> HttpResponse {
+    let cred_id = path.into_inner();
+    let nickname = request.into_inner().nickname;
+    match db
+        .rename_passkey(claims.uuid.to_string(), cred_id, nickname)
+        .await
+    {
+        Ok(true) => HttpResponse::Ok().body("Passkey renamed successfully"),
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "Passkey not found" })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error renaming passkey: {}", err) })),
+    }
+}
+
+#[delete("{cred_id}")]
+pub async fn revoke_passkey(
+    claims: Claims,
+    path: Path<String>,
+    db: Data<dyn UserRepository>,
+) -> HttpResponse {
+    let cred_id = path.into_inner();
+    match db
+        .remove_passkey(claims.uuid.to_string(), cred_id.clone())
+        .await
+    {
+        Ok(true) => HttpResponse::Ok().body("Passkey revoked successfully"),
+        // Work out why nothing was removed so the client gets a useful answer
+        Ok(false) => match db.get_user_by_id(claims.uuid.to_string()).await {
+            Ok(Some(user)) if user.keys.iter().any(|k| k.cred_id().to_string() == cred_id) => {
+                HttpResponse::Conflict()
+                    .json(json!({ "error": "Cannot revoke the last passkey on an account" }))
+            }
+            Ok(_) => HttpResponse::NotFound().json(json!({ "error": "Passkey not found" })),
+            Err(err) => HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching user: {}", err) })),
+        },
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error revoking passkey: {}", err) })),
+    }
+}
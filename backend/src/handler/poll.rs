@@ -1,38 +1,79 @@
 use crate::db::poll_crud::PollRepository;
-use crate::db::user_crud::UserRepository;
-use crate::models::poll::{Poll, ResultsQuery, ServerEvents};
-use crate::models::user::Votes;
+use crate::models::jwt::Claims;
+use crate::models::poll::{
+    validate_score_rules, validate_selection_rules, validate_voter_roll, validate_window,
+    BallotChoice, CreatePollRequest, FieldError, Poll, PollResults, PollStatus, ResultsQuery,
+    SchedulePollRequest, ServerEvents, TransitionOutcome, UpdatePollRequest, Viewer, VoteOutcome,
+    VotingMethod,
+};
+use crate::tally::score::OptionScore;
 use actix_web::body::MessageBody;
 use actix_web::{
-    delete, get, post,
-    web::{Data, Json, Path, Query},
+    delete, get, patch, post, put,
+    web::{Bytes, Data, Json, Path, Query},
     HttpResponse,
 };
+use chrono::Utc;
 use serde::Deserialize;
 use serde_json::json;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 
+/// Answers a request that failed validation with its field-level problems.
+fn invalid_poll(fields: Vec<FieldError>) -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(json!({
+        "error": "Invalid poll",
+        "fields": fields
+    }))
+}
+
 #[post("polls")]
-pub async fn add_polls(db: Data<dyn PollRepository>, request: Json<Poll>) -> HttpResponse {
-    println!("Received Poll Data: {:#?}", request);
-    match db.create_poll(request.into_inner()).await {
+pub async fn add_polls(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    request: Json<CreatePollRequest>,
+) -> HttpResponse {
+    log::debug!("Received poll data: {:?}", request);
+    let request = match request.into_inner().validate(Utc::now()) {
+        Ok(request) => request,
+        Err(fields) => return invalid_poll(fields),
+    };
+    // The creator is whoever holds the token, not whatever the client claims
+    match db.create_poll(request, claims.uuid.to_string()).await {
         Ok(poll) => HttpResponse::Ok().json(poll),
         Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
     }
 }
 
+/// Drafts and scheduled polls are only visible to their owner and admins.
+fn is_visible(poll: &Poll, claims: Option<&Claims>) -> bool {
+    poll.status.is_published() || claims.is_some_and(|claims| claims.can_manage(&poll.creator))
+}
+
+fn viewer(claims: Option<&Claims>) -> Viewer {
+    match claims {
+        Some(claims) if claims.is_admin() => Viewer::Admin,
+        Some(claims) => Viewer::Member(claims.uuid.to_string()),
+        None => Viewer::Anonymous,
+    }
+}
+
 #[get("polls/{poll_id}")]
-pub async fn fetch_polls(db: Data<dyn PollRepository>, path: Path<i64>) -> HttpResponse {
+pub async fn fetch_polls(
+    claims: Option<Claims>,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
     let poll_id = path.into_inner();
     if poll_id == 0 {
-        match db.fetch_all().await {
+        match db.fetch_all(viewer(claims.as_ref())).await {
             Ok(polls) => HttpResponse::Ok().json(polls),
             Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
         }
     } else {
         match db.get_poll(poll_id).await {
-            Ok(polls) => HttpResponse::Ok().json(polls),
+            Ok(Some(poll)) if is_visible(&poll, claims.as_ref()) => HttpResponse::Ok().json(poll),
+            Ok(_) => HttpResponse::NotFound().json(json!({ "error": "Poll not found" })),
             Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
         }
     }
@@ -41,72 +82,396 @@ pub async fn fetch_polls(db: Data<dyn PollRepository>, path: Path<i64>) -> HttpR
 #[derive(Debug, Deserialize, Clone)]
 pub struct VoteOption {
     option_id: i64,
-    username: String,
+}
+
+/// A ballot selecting one or more options, or scoring each option in a score poll.
+#[derive(Debug, Deserialize, Clone)]
+pub struct BallotRequest {
+    option_ids: Option<Vec<i64>>,
+    scores: Option<Vec<OptionScore>>,
+}
+
+/// What a vote request puts on the ballot: the JSON ballot if a body was sent, else
+/// `?option_id=`. A body that does not parse, or that sets both `option_ids` and `scores`, is
+/// rejected rather than falling back to the query.
+fn ballot(query: Option<Query<VoteOption>>, body: &[u8]) -> Result<BallotChoice, HttpResponse> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return match query {
+            Some(query) => Ok(BallotChoice::Options(vec![query.option_id])),
+            None => Err(missing_ballot()),
+        };
+    }
+    let request: BallotRequest = serde_json::from_slice(body).map_err(|err| {
+        HttpResponse::BadRequest().json(json!({ "error": format!("Invalid ballot: {}", err) }))
+    })?;
+    match request {
+        BallotRequest {
+            option_ids: Some(_),
+            scores: Some(_),
+        } => Err(HttpResponse::BadRequest().json(json!({
+            "error": "Send either option_ids or scores, not both"
+        }))),
+        BallotRequest {
+            scores: Some(scores),
+            ..
+        } => Ok(BallotChoice::Scores(scores)),
+        BallotRequest {
+            option_ids: Some(option_ids),
+            ..
+        } => Ok(BallotChoice::Options(option_ids)),
+        _ => Err(missing_ballot()),
+    }
+}
+
+fn missing_ballot() -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": "Send option_ids or scores in the body, or option_id in the query"
+    }))
+}
+
+/// Answers a vote request from what the repository did with it.
+fn vote_response(
+    outcome: Result<VoteOutcome, Box<dyn std::error::Error>>,
+    success: &'static str,
+) -> HttpResponse {
+    match outcome {
+        Ok(VoteOutcome::Recorded) => HttpResponse::Ok().body(success),
+        Ok(VoteOutcome::AlreadyVoted) => HttpResponse::Conflict().json(json!({
+            "error": "You have already voted in this poll"
+        })),
+        Ok(VoteOutcome::PollNotFound) => {
+            HttpResponse::NotFound().json(json!({ "error": "Poll not found" }))
+        }
+        Ok(VoteOutcome::PollNotActive) => HttpResponse::Conflict().json(json!({
+            "error": "This poll is not accepting votes"
+        })),
+        Ok(VoteOutcome::PollExpired) => HttpResponse::Conflict().json(json!({
+            "error": "The deadline for this poll has passed"
+        })),
+        Ok(VoteOutcome::NotYetOpen) => HttpResponse::Conflict().json(json!({
+            "error": "This poll has not opened yet"
+        })),
+        Ok(VoteOutcome::InvalidBallot(problem)) => {
+            HttpResponse::BadRequest().json(json!({ "error": problem }))
+        }
+        Ok(VoteOutcome::NotVoted) => HttpResponse::Conflict().json(json!({
+            "error": "You have not voted in this poll"
+        })),
+        Ok(VoteOutcome::ChangesDisabled) => HttpResponse::Forbidden().json(json!({
+            "error": "Votes in this poll cannot be changed"
+        })),
+        Ok(VoteOutcome::NotOnRoll) => HttpResponse::Forbidden().json(json!({
+            "error": "You are not on this poll's voter roll"
+        })),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
 }
 
 #[post("polls/{poll_id}/vote")]
 pub async fn cast_vote(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+    query: Option<Query<VoteOption>>,
+    body: Bytes,
+) -> HttpResponse {
+    let choice = match ballot(query, &body) {
+        Ok(choice) => choice,
+        Err(response) => return response,
+    };
+    let outcome = db
+        .vote_poll(path.into_inner(), choice, claims.uuid.to_string())
+        .await;
+    vote_response(outcome, "Vote casted successfully")
+}
+
+#[put("polls/{poll_id}/vote")]
+pub async fn change_vote(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+    query: Option<Query<VoteOption>>,
+    body: Bytes,
+) -> HttpResponse {
+    let choice = match ballot(query, &body) {
+        Ok(choice) => choice,
+        Err(response) => return response,
+    };
+    let outcome = db
+        .change_vote(path.into_inner(), choice, claims.uuid.to_string())
+        .await;
+    vote_response(outcome, "Vote changed successfully")
+}
+
+#[delete("polls/{poll_id}/vote")]
+pub async fn retract_vote(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
+    let outcome = db
+        .retract_vote(path.into_inner(), claims.uuid.to_string())
+        .await;
+    vote_response(outcome, "Vote withdrawn successfully")
+}
+
+/// Loads a poll the caller wants to change, answering 404 if it does not exist and 403 unless
+/// the caller owns it or is an admin.
+async fn authorize_poll_change(
+    db: &dyn PollRepository,
+    claims: &Claims,
+    poll_id: i64,
+) -> Result<Poll, HttpResponse> {
+    match db.get_poll(poll_id).await {
+        Ok(Some(poll)) if claims.can_manage(&poll.creator) => Ok(poll),
+        Ok(Some(_)) => Err(HttpResponse::Forbidden().json(json!({
+            "error": "Only the poll owner or an admin may change this poll"
+        }))),
+        Ok(None) => Err(HttpResponse::NotFound().json(json!({ "error": "Poll not found" }))),
+        Err(err) => Err(HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error fetching poll: {}", err) }))),
+    }
+}
+
+#[patch("polls/{poll_id}")]
+pub async fn update_poll(
+    claims: Claims,
     db: Data<dyn PollRepository>,
-    db2: Data<dyn UserRepository>,
     path: Path<i64>,
-    query: Query<VoteOption>,
+    request: Json<UpdatePollRequest>,
 ) -> HttpResponse {
-    let query_opts = query;
-    let option_id = query_opts.option_id;
-    let username = query_opts.username.to_string();
     let poll_id = path.into_inner();
-    let vote = Votes {
-        poll_id: poll_id,
-        option_id: option_id,
+    let poll = match authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        Ok(poll) => poll,
+        Err(response) => return response,
+    };
+    let request = match request.into_inner().validate(Utc::now()) {
+        Ok(request) => request,
+        Err(fields) => return invalid_poll(fields),
     };
-    db2.update_user(username.clone(), vote).await.unwrap();
-    match db.vote_poll(poll_id, option_id, username).await {
-        Ok(_) => HttpResponse::Ok().body("Vote casted successfully"),
+    if let Err(fields) = validate_window(
+        request.opens_at.or(poll.opens_at),
+        request.expiration_date.or(poll.expiration_date),
+    ) {
+        return invalid_poll(fields);
+    }
+    // Limits stored for a multi-option poll do not carry over to a single-choice one
+    let voting_method = request.voting_method.unwrap_or(poll.voting_method);
+    let (min_selections, max_selections) = match voting_method {
+        VotingMethod::SingleChoice | VotingMethod::Score => {
+            (request.min_selections, request.max_selections)
+        }
+        VotingMethod::Approval | VotingMethod::RankedChoice | VotingMethod::Schulze => (
+            request.min_selections.or(Some(poll.min_selections)),
+            request.max_selections.or(poll.max_selections),
+        ),
+    };
+    let option_count = request
+        .options
+        .as_ref()
+        .map_or(poll.options.len(), |options| options.len());
+    if let Err(fields) =
+        validate_selection_rules(voting_method, min_selections, max_selections, option_count)
+    {
+        return invalid_poll(fields);
+    }
+    let (min_score, max_score, star_runoff) = match voting_method {
+        VotingMethod::Score => (
+            request.min_score.or(Some(poll.min_score)),
+            request.max_score.or(Some(poll.max_score)),
+            request.star_runoff.unwrap_or(poll.star_runoff),
+        ),
+        _ => (
+            request.min_score,
+            request.max_score,
+            request.star_runoff.unwrap_or(false),
+        ),
+    };
+    if let Err(fields) = validate_score_rules(voting_method, min_score, max_score, star_runoff) {
+        return invalid_poll(fields);
+    }
+    let voter_roll = request.voter_roll.as_ref().or(poll.voter_roll.as_ref());
+    if let Err(fields) = validate_voter_roll(voting_method, voter_roll) {
+        return invalid_poll(fields);
+    }
+    if !request.editable_in().contains(&poll.status) {
+        return HttpResponse::Conflict().json(json!({
+            "error": format!("These changes cannot be made to a {} poll", poll.status),
+            "status": poll.status,
+        }));
+    }
+    match db.update_poll(poll_id, request).await {
+        Ok(Some(poll)) => HttpResponse::Ok().json(poll),
+        // The status changed since it was checked above
+        Ok(None) => HttpResponse::Conflict().json(json!({
+            "error": "The poll changed while it was being edited; please try again"
+        })),
         Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
     }
 }
+
 #[post("polls/{poll_id}/reset")]
-pub async fn reset_vote(db: Data<dyn PollRepository>, path: Path<i64>) -> HttpResponse {
+pub async fn reset_vote(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
     let poll_id = path.into_inner();
-    match db.update_poll(poll_id, "reset".to_string()).await {
+    if let Err(response) = authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        return response;
+    }
+    match db.reset_votes(poll_id).await {
         Ok(_) => HttpResponse::Ok().body("Poll reset successful"),
         Err(e) => {
-            eprintln!("Error deleting poll: {:?}", e);
-            HttpResponse::InternalServerError().body("Failed to delete poll")
+            eprintln!("Error resetting poll: {:?}", e);
+            HttpResponse::InternalServerError().body("Failed to reset poll")
         }
     }
 }
 
-#[post("polls/{poll_id}/close")]
-pub async fn close_poll(db: Data<dyn PollRepository>, path: Path<i64>) -> HttpResponse {
-    let poll_id = path.into_inner();
-    match db.update_poll(poll_id, "close".to_string()).await {
-        Ok(_) => HttpResponse::Ok().body("Poll closed successfully"),
+/// Answers a status change from what the repository did with it.
+fn transition_response(
+    outcome: Result<TransitionOutcome, Box<dyn std::error::Error>>,
+    to: PollStatus,
+) -> HttpResponse {
+    match outcome {
+        Ok(TransitionOutcome::Done) => HttpResponse::Ok().json(json!({ "status": to })),
+        Ok(TransitionOutcome::PollNotFound) => {
+            HttpResponse::NotFound().json(json!({ "error": "Poll not found" }))
+        }
+        Ok(TransitionOutcome::Illegal(from)) => HttpResponse::Conflict().json(json!({
+            "error": format!("A {} poll cannot become {}", from, to),
+            "status": from,
+        })),
         Err(e) => {
-            eprintln!("Error deleting poll: {:?}", e);
-            HttpResponse::InternalServerError().body("Failed to delete poll")
+            eprintln!("Error changing poll status: {:?}", e);
+            HttpResponse::InternalServerError().body("Failed to change poll status")
         }
     }
 }
 
+#[post("polls/{poll_id}/schedule")]
+pub async fn schedule_poll(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+    request: Json<SchedulePollRequest>,
+) -> HttpResponse {
+    let poll_id = path.into_inner();
+    let poll = match authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        Ok(poll) => poll,
+        Err(response) => return response,
+    };
+    let opens_at = request.opens_at;
+    if opens_at <= Utc::now() {
+        return invalid_poll(vec![FieldError::new("opens_at", "must be in the future")]);
+    }
+    if let Err(fields) = validate_window(Some(opens_at), poll.expiration_date) {
+        return invalid_poll(fields);
+    }
+    transition_response(
+        db.schedule_poll(poll_id, opens_at).await,
+        PollStatus::Scheduled,
+    )
+}
+
+#[post("polls/{poll_id}/activate")]
+pub async fn activate_poll(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
+    let poll_id = path.into_inner();
+    if let Err(response) = authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        return response;
+    }
+    transition_response(db.activate_poll(poll_id).await, PollStatus::Active)
+}
+
+#[post("polls/{poll_id}/close")]
+pub async fn close_poll(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
+    let poll_id = path.into_inner();
+    if let Err(response) = authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        return response;
+    }
+    transition_response(db.close_poll(poll_id).await, PollStatus::Closed)
+}
+
+#[post("polls/{poll_id}/expire")]
+pub async fn expire_poll(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
+    let poll_id = path.into_inner();
+    if let Err(response) = authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        return response;
+    }
+    transition_response(db.expire_poll(poll_id).await, PollStatus::Expired)
+}
+
+#[post("polls/{poll_id}/archive")]
+pub async fn archive_poll(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
+    let poll_id = path.into_inner();
+    if let Err(response) = authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        return response;
+    }
+    transition_response(db.archive_poll(poll_id).await, PollStatus::Archived)
+}
+
+/// Works out a poll's results, loading its ballots if it tallies from them.
+async fn tally(
+    db: &dyn PollRepository,
+    poll: Poll,
+) -> Result<PollResults, Box<dyn std::error::Error + Send + Sync>> {
+    let ballots = if poll.voting_method.keeps_ballots() {
+        db.ballots(poll.poll_id).await?
+    } else {
+        Vec::new()
+    };
+    Ok(PollResults::new(poll, &ballots))
+}
+
 #[get("/polls/{poll_id}/results")]
 async fn poll_results(
+    claims: Option<Claims>,
     db: Data<dyn PollRepository>,
     path: Path<i64>,
     query: Query<ResultsQuery>,
 ) -> HttpResponse {
     let poll_id = path.into_inner();
+    let poll = match db.get_poll(poll_id).await {
+        Ok(Some(poll)) if is_visible(&poll, claims.as_ref()) => poll,
+        _ => {
+            return HttpResponse::NotFound().json(json!({
+                "error": "Poll not found"
+            }))
+        }
+    };
     let db_clone = db.clone();
     if query.live {
         let (tx, rx) = mpsc::channel(1024);
 
         tokio::spawn(async move {
             loop {
-                match db_clone.get_poll(poll_id).await {
-                    Ok(poll) => {
+                let results = match db_clone.get_poll(poll_id).await {
+                    Ok(Some(poll)) => tally(db_clone.get_ref(), poll).await.map(Some),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                };
+                match results {
+                    Ok(results) => {
                         tx.send(format!(
                             "data: {}\n\n",
-                            serde_json::to_string(&poll).unwrap()
+                            serde_json::to_string(&results).unwrap()
                         ))
                         .await
                         .unwrap();
@@ -126,22 +491,26 @@ async fn poll_results(
         // Returning the response with the correct streaming headers
         return HttpResponse::Ok()
             .insert_header(("Content-Type", "text/event-stream"))
-            .insert_header(("Access-Control-Allow-Origin", "*"))
             .streaming(body);
     }
 
     // If not live, return the current poll data
-    match db.get_poll(poll_id).await {
-        Ok(poll) => HttpResponse::Ok().json(poll),
-        Err(_) => HttpResponse::NotFound().json(json!({
-            "error": "Poll not found"
-        })),
+    match tally(db.get_ref(), poll).await {
+        Ok(results) => HttpResponse::Ok().json(results),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
     }
 }
 
 #[delete("polls/delete-poll/{poll_id}")]
-pub async fn delete_poll(db: Data<dyn PollRepository>, path: Path<i64>) -> HttpResponse {
+pub async fn delete_poll(
+    claims: Claims,
+    db: Data<dyn PollRepository>,
+    path: Path<i64>,
+) -> HttpResponse {
     let poll_id = path.into_inner(); // Extract the poll_id from the path
+    if let Err(response) = authorize_poll_change(db.get_ref(), &claims, poll_id).await {
+        return response;
+    }
 
     match db.delete_poll(poll_id).await {
         Ok(_) => HttpResponse::Ok().body("Poll deleted successfully"),
@@ -0,0 +1,84 @@
+use crate::db::{audit_crud::AuditRepository, user_crud::UserRepository};
+use crate::handler::middleware::auth_middleware::CheckAuth;
+use crate::models::audit::{AuditEvent, AuditKind};
+use crate::models::jwt::{encode_recovery_jwt, Claims, JwtKeys};
+use crate::models::recovery::{hash_recovery_code, RecoveryCodes, RecoveryRequest};
+use actix_web::web::{Data, Json};
+use actix_web::{post, HttpResponse};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Exchanges an unused recovery code for a short-lived token that can only enroll a passkey
+/// through the `passkeys` endpoints. The code is burned whether or not enrollment follows.
+#[post("recover")]
+pub async fn recover_account(
+    request: Json<RecoveryRequest>,
+    db: Data<dyn UserRepository>,
+    audit: Data<dyn AuditRepository>,
+    keys: Data<JwtKeys>,
+) -> HttpResponse {
+    let RecoveryRequest { user_name, code } = request.into_inner();
+    let user = match db
+        .consume_recovery_code(user_name, hash_recovery_code(&code))
+        .await
+    {
+        Ok(Some(user)) => user,
+        // Unknown users and wrong codes look the same to the caller
+        Ok(None) => {
+            return HttpResponse::Unauthorized()
+                .json(json!({ "error": "Invalid or already used recovery code" }))
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error checking recovery code: {}", err) }))
+        }
+    };
+    let user_id = match Uuid::parse_str(&user.user_id) {
+        Ok(user_id) => user_id,
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": "Corrupt user record" }))
+        }
+    };
+
+    let event = AuditEvent::new(
+        AuditKind::RecoveryCodeUsed,
+        Some(user.user_id.clone()),
+        format!(
+            "Recovery code used; {} codes left",
+            user.recovery_codes.len().saturating_sub(1)
+        ),
+    );
+    if let Err(e) = audit.record(event).await {
+        eprintln!("Failed to record audit event: {}", e);
+    }
+
+    match encode_recovery_jwt(&keys, &user_id) {
+        Ok((token, claims)) => HttpResponse::Ok().json(json!({
+            "recovery_token": token,
+            "expires_at": claims.exp,
+            "user_id": user.user_id,
+            "user_name": user.user_name,
+        })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error generating token: {}", err) })),
+    }
+}
+
+/// Replaces the caller's recovery codes with a new set, invalidating every old code.
+#[post("recovery_codes", wrap = "CheckAuth::new()")]
+pub async fn regenerate_recovery_codes(
+    claims: Claims,
+    db: Data<dyn UserRepository>,
+) -> HttpResponse {
+    let recovery = RecoveryCodes::generate();
+    match db
+        .set_recovery_codes(claims.uuid.to_string(), recovery.hashes)
+        .await
+    {
+        Ok(true) => HttpResponse::Ok().json(json!({ "recovery_codes": recovery.codes })),
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "User not found" })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error storing recovery codes: {}", err) })),
+    }
+}
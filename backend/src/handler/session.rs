@@ -0,0 +1,148 @@
+use crate::db::{
+    audit_crud::AuditRepository, token_crud::TokenRepository, user_crud::UserRepository,
+};
+use crate::handler::middleware::auth_middleware::CheckAuth;
+use crate::models::audit::{AuditEvent, AuditKind};
+use crate::models::jwt::{encode_jwt, Claims, JwtKeys};
+use crate::models::token::{RefreshOutcome, RefreshRequest, RefreshToken};
+use crate::models::user::Role;
+use actix_web::web::{Data, Json};
+use actix_web::{get, post, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use uuid::Uuid;
+
+/// Access and refresh token handed to a client when a session starts or rotates.
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Issues an access token carrying `roles` plus a refresh token in `family_id`, storing the
+/// refresh token's hash.
+pub async fn issue_tokens(
+    tokens: &dyn TokenRepository,
+    keys: &JwtKeys,
+    user_id: &Uuid,
+    roles: Vec<Role>,
+    family_id: Uuid,
+) -> Result<IssuedTokens, Box<dyn std::error::Error + Send + Sync>> {
+    let (access_token, claims) = encode_jwt(keys, user_id, &family_id, roles)?;
+    let refresh_token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    let now = Utc::now();
+    tokens
+        .store_refresh_token(RefreshToken {
+            token_hash: sha256::digest(refresh_token.as_str()),
+            user_id: user_id.to_string(),
+            family_id: family_id.to_string(),
+            access_jti: claims.jti.to_string(),
+            access_expires_at: DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or(now),
+            expires_at: now + keys.refresh_token_lifetime(),
+            created_at: now,
+        })
+        .await?;
+
+    Ok(IssuedTokens {
+        access_token,
+        refresh_token,
+    })
+}
+
+#[post("refresh")]
+pub async fn refresh_session(
+    request: Json<RefreshRequest>,
+    tokens: Data<dyn TokenRepository>,
+    keys: Data<JwtKeys>,
+    users: Data<dyn UserRepository>,
+    audit: Data<dyn AuditRepository>,
+) -> HttpResponse {
+    let token_hash = sha256::digest(request.refresh_token.as_str());
+    let outcome = match tokens.consume_refresh_token(token_hash).await {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error checking refresh token: {}", err) }))
+        }
+    };
+
+    match outcome {
+        RefreshOutcome::Valid(previous) => {
+            let (user_id, family_id) = match (
+                Uuid::parse_str(&previous.user_id),
+                Uuid::parse_str(&previous.family_id),
+            ) {
+                (Ok(user_id), Ok(family_id)) => (user_id, family_id),
+                _ => {
+                    return HttpResponse::InternalServerError()
+                        .json(json!({ "error": "Corrupt refresh token record" }))
+                }
+            };
+            // Roles are read afresh so that grants and revocations apply from the next refresh
+            let roles = match users.get_user_by_id(previous.user_id.clone()).await {
+                Ok(Some(user)) => user.roles,
+                Ok(None) => {
+                    return HttpResponse::Unauthorized()
+                        .json(json!({ "error": "Invalid or expired refresh token" }))
+                }
+                Err(err) => {
+                    return HttpResponse::InternalServerError()
+                        .json(json!({ "error": format!("Error fetching user: {}", err) }))
+                }
+            };
+            match issue_tokens(tokens.get_ref(), &keys, &user_id, roles, family_id).await {
+                Ok(issued) => HttpResponse::Ok().json(json!({
+                    "token": issued.access_token,
+                    "refresh_token": issued.refresh_token,
+                })),
+                Err(err) => HttpResponse::InternalServerError()
+                    .json(json!({ "error": format!("Error generating token: {}", err) })),
+            }
+        }
+        RefreshOutcome::Reused(previous) => {
+            // Somebody else holds a copy of this chain, so nobody gets to keep it
+            let event = AuditEvent::new(
+                AuditKind::RefreshTokenReuse,
+                Some(previous.user_id),
+                format!(
+                    "Refresh token reused; revoking family {}",
+                    previous.family_id
+                ),
+            );
+            if let Err(e) = audit.record(event).await {
+                eprintln!("Failed to record audit event: {}", e);
+            }
+            if let Err(e) = tokens.revoke_family(previous.family_id).await {
+                eprintln!("Failed to revoke token family: {}", e);
+            }
+            HttpResponse::Unauthorized()
+                .json(json!({ "error": "Refresh token has already been used" }))
+        }
+        RefreshOutcome::Invalid => HttpResponse::Unauthorized()
+            .json(json!({ "error": "Invalid or expired refresh token" })),
+    }
+}
+
+#[post("logout", wrap = "CheckAuth::new()")]
+pub async fn logout(claims: Claims, tokens: Data<dyn TokenRepository>) -> HttpResponse {
+    match tokens.revoke_family(claims.fid.to_string()).await {
+        Ok(_) => HttpResponse::Ok().body("Logged out successfully"),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error revoking session: {}", err) })),
+    }
+}
+
+#[post("logout_all", wrap = "CheckAuth::new()")]
+pub async fn logout_all(claims: Claims, tokens: Data<dyn TokenRepository>) -> HttpResponse {
+    match tokens.revoke_user_sessions(claims.uuid.to_string()).await {
+        Ok(_) => HttpResponse::Ok().body("Logged out of all sessions successfully"),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error revoking sessions: {}", err) })),
+    }
+}
+
+/// Lets other services verify access tokens without sharing a secret.
+#[get("jwks.json")]
+pub async fn jwks(keys: Data<JwtKeys>) -> HttpResponse {
+    HttpResponse::Ok().json(keys.jwks())
+}
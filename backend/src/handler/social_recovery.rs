@@ -0,0 +1,363 @@
+use crate::config::AppConfig;
+use crate::db::{
+    audit_crud::AuditRepository, social_recovery_crud::SocialRecoveryRepository,
+    user_crud::UserRepository,
+};
+use crate::handler::middleware::auth_middleware::CheckAuth;
+use crate::models::audit::{AuditEvent, AuditKind};
+use crate::models::jwt::{encode_recovery_jwt, Claims, JwtKeys};
+use crate::models::social_recovery::{
+    ApproveSocialRecoveryRequest, CompleteSocialRecoveryRequest, NominateTrusteesRequest,
+    RecoverySetup, SocialRecoveryRequest, StartSocialRecoveryRequest, TrusteeShare,
+};
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, post, put, HttpResponse};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use secret_sharing_algos::Share;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Opts in to social recovery, or replaces the current trustees. A fresh recovery secret is
+/// split so that any `threshold` of the trustees can approve a recovery; the secret itself is
+/// never stored. Each share waits for its trustee to collect it from `GET shares`, after which
+/// the server keeps only the secret's hash.
+#[put("trustees", wrap = "CheckAuth::new()")]
+pub async fn nominate_trustees(
+    claims: Claims,
+    request: Json<NominateTrusteesRequest>,
+    users: Data<dyn UserRepository>,
+    recovery: Data<dyn SocialRecoveryRepository>,
+) -> HttpResponse {
+    let NominateTrusteesRequest {
+        trustees,
+        threshold,
+    } = request.into_inner();
+    let owner_id = claims.uuid.to_string();
+
+    let mut trustee_ids: Vec<String> = Vec::with_capacity(trustees.len());
+    for user_name in &trustees {
+        let trustee = match users.get_user(user_name.clone()).await {
+            Ok(Some(trustee)) => trustee,
+            Ok(None) => {
+                return HttpResponse::BadRequest()
+                    .json(json!({ "error": format!("Unknown trustee: {}", user_name) }))
+            }
+            Err(err) => {
+                return HttpResponse::InternalServerError()
+                    .json(json!({ "error": format!("Error fetching user: {}", err) }))
+            }
+        };
+        if trustee.user_id == owner_id {
+            return HttpResponse::BadRequest()
+                .json(json!({ "error": "You cannot be your own trustee" }));
+        }
+        if trustee_ids.contains(&trustee.user_id) {
+            return HttpResponse::BadRequest()
+                .json(json!({ "error": format!("Duplicate trustee: {}", user_name) }));
+        }
+        trustee_ids.push(trustee.user_id);
+    }
+
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let shares = match secret_sharing_algos::split(&secret, threshold as usize, trustee_ids.len()) {
+        Ok(shares) => shares,
+        Err(err) => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid trustee setup: {}", err),
+            }))
+        }
+    };
+    let trustee_shares = trustee_ids
+        .iter()
+        .zip(shares)
+        .map(|(trustee_id, share)| TrusteeShare {
+            owner_id: owner_id.clone(),
+            trustee_id: trustee_id.clone(),
+            share: share.to_string(),
+        })
+        .collect();
+    let setup = RecoverySetup {
+        owner_id,
+        threshold,
+        trustee_ids,
+        secret_hash: sha256::digest(&secret[..]),
+        created_at: Utc::now(),
+    };
+
+    match recovery.set_trustees(setup, trustee_shares).await {
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "trustees": trustees,
+            "threshold": threshold,
+        })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error storing trustees: {}", err) })),
+    }
+}
+
+#[delete("trustees", wrap = "CheckAuth::new()")]
+pub async fn remove_trustees(
+    claims: Claims,
+    recovery: Data<dyn SocialRecoveryRepository>,
+) -> HttpResponse {
+    match recovery.remove_trustees(claims.uuid.to_string()).await {
+        Ok(true) => HttpResponse::Ok().body("Social recovery disabled"),
+        Ok(false) => {
+            HttpResponse::NotFound().json(json!({ "error": "Social recovery is not set up" }))
+        }
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error removing trustees: {}", err) })),
+    }
+}
+
+/// Starts a recovery for an owner who has lost their passkeys. The returned `request_secret`
+/// is needed to complete it, so that only whoever started the request can use the approvals.
+#[post("start")]
+pub async fn start_social_recovery(
+    request: Json<StartSocialRecoveryRequest>,
+    users: Data<dyn UserRepository>,
+    recovery: Data<dyn SocialRecoveryRepository>,
+    config: Data<AppConfig>,
+) -> HttpResponse {
+    let user_name = request.into_inner().user_name;
+    let owner = match users.get_user(user_name).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return HttpResponse::NotFound().json(json!({ "error": "User not found" })),
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching user: {}", err) }))
+        }
+    };
+    let setup = match recovery.get_setup(owner.user_id.clone()).await {
+        Ok(Some(setup)) => setup,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(json!({ "error": "Social recovery is not set up for this account" }))
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching trustees: {}", err) }))
+        }
+    };
+
+    let request_secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let now = Utc::now();
+    let request = SocialRecoveryRequest {
+        request_id: Uuid::new_v4().to_string(),
+        owner_id: owner.user_id,
+        owner_name: owner.user_name,
+        threshold: setup.threshold,
+        request_secret_hash: sha256::digest(request_secret.as_str()),
+        approvals: vec![],
+        expires_at: now + Duration::hours(config.accounts.social_recovery_hours),
+        created_at: now,
+    };
+
+    match recovery.create_request(request.clone()).await {
+        Ok(_) => HttpResponse::Ok().json(json!({
+            "request_id": request.request_id,
+            "request_secret": request_secret,
+            "threshold": request.threshold,
+            "expires_at": request.expires_at,
+        })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error starting recovery: {}", err) })),
+    }
+}
+
+/// Hands the caller the shares they were given as a trustee since they last asked. They are
+/// returned only once; trustees keep them to approve recoveries later.
+#[get("shares", wrap = "CheckAuth::new()")]
+pub async fn collect_shares(
+    claims: Claims,
+    recovery: Data<dyn SocialRecoveryRepository>,
+) -> HttpResponse {
+    match recovery.take_shares(claims.uuid.to_string()).await {
+        Ok(shares) => HttpResponse::Ok().json(
+            shares
+                .iter()
+                .map(|share| {
+                    json!({
+                        "owner_id": share.owner_id,
+                        "share": share.share,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error collecting shares: {}", err) })),
+    }
+}
+
+/// Recoveries the caller has been asked to approve as a trustee.
+#[get("requests", wrap = "CheckAuth::new()")]
+pub async fn list_recovery_requests(
+    claims: Claims,
+    recovery: Data<dyn SocialRecoveryRepository>,
+) -> HttpResponse {
+    let trustee_id = claims.uuid.to_string();
+    match recovery
+        .pending_requests_for_trustee(trustee_id.clone())
+        .await
+    {
+        Ok(requests) => HttpResponse::Ok().json(
+            requests
+                .iter()
+                .map(|request| {
+                    json!({
+                        "request_id": request.request_id,
+                        "owner_id": request.owner_id,
+                        "owner_name": request.owner_name,
+                        "approvals": request.approvals.len(),
+                        "threshold": request.threshold,
+                        "approved": request
+                            .approvals
+                            .iter()
+                            .any(|approval| approval.trustee_id == trustee_id),
+                        "created_at": request.created_at,
+                        "expires_at": request.expires_at,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error fetching recovery requests: {}", err) })),
+    }
+}
+
+/// Submits the caller's share, collected for the request's owner, to a recovery. Trustees
+/// should confirm with the owner through some other channel before approving.
+#[post("requests/{request_id}/approve", wrap = "CheckAuth::new()")]
+pub async fn approve_recovery(
+    claims: Claims,
+    path: Path<String>,
+    request: Json<ApproveSocialRecoveryRequest>,
+    recovery: Data<dyn SocialRecoveryRepository>,
+) -> HttpResponse {
+    let share = request.into_inner().share;
+    if let Err(err) = share.parse::<Share>() {
+        return HttpResponse::BadRequest()
+            .json(json!({ "error": format!("Invalid share: {}", err) }));
+    }
+
+    match recovery
+        .approve_request(path.into_inner(), claims.uuid.to_string(), share)
+        .await
+    {
+        Ok(true) => HttpResponse::Ok().body("Recovery approved"),
+        Ok(false) => HttpResponse::NotFound()
+            .json(json!({ "error": "No pending recovery request for you to approve" })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error approving recovery: {}", err) })),
+    }
+}
+
+/// Rebuilds the recovery secret from the approved shares and, if it matches, hands out a
+/// recovery token that can enroll a new passkey.
+#[post("requests/{request_id}/complete")]
+pub async fn complete_social_recovery(
+    path: Path<String>,
+    request: Json<CompleteSocialRecoveryRequest>,
+    recovery: Data<dyn SocialRecoveryRepository>,
+    audit: Data<dyn AuditRepository>,
+    keys: Data<JwtKeys>,
+) -> HttpResponse {
+    let request_id = path.into_inner();
+    let secret_hash = sha256::digest(request.request_secret.as_str());
+
+    let pending = match recovery
+        .get_request(request_id.clone(), secret_hash.clone())
+        .await
+    {
+        Ok(Some(pending)) if pending.approvals.len() < pending.threshold as usize => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "Not enough trustees have approved yet",
+                "approvals": pending.approvals.len(),
+                "threshold": pending.threshold,
+            }))
+        }
+        Ok(Some(pending)) => pending,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(json!({ "error": "Recovery request not found or expired" }))
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching recovery request: {}", err) }))
+        }
+    };
+    let setup = match recovery.get_setup(pending.owner_id.clone()).await {
+        Ok(Some(setup)) => setup,
+        Ok(None) => {
+            return HttpResponse::Conflict()
+                .json(json!({ "error": "Social recovery is no longer set up for this account" }))
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching trustees: {}", err) }))
+        }
+    };
+
+    // Checked before taking the request, so a bad share does not throw away the approvals
+    let shares: Result<Vec<Share>, _> = pending
+        .approvals
+        .iter()
+        .map(|approval| approval.share.parse())
+        .collect();
+    let verified = shares
+        .and_then(|shares| secret_sharing_algos::reconstruct(&shares))
+        .map(|secret| sha256::digest(secret.as_slice()) == setup.secret_hash)
+        .unwrap_or(false);
+    if !verified {
+        return HttpResponse::Conflict().json(
+            json!({ "error": "The approved shares do not reconstruct the recovery secret" }),
+        );
+    }
+
+    // Taking the request means each set of approvals yields a single token
+    let completed = match recovery.take_request(request_id, secret_hash).await {
+        Ok(Some(completed)) => completed,
+        Ok(None) => {
+            return HttpResponse::NotFound()
+                .json(json!({ "error": "Recovery request not found or expired" }))
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": format!("Error fetching recovery request: {}", err) }))
+        }
+    };
+
+    let owner_id = match Uuid::parse_str(&completed.owner_id) {
+        Ok(owner_id) => owner_id,
+        Err(_) => {
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": "Corrupt recovery request" }))
+        }
+    };
+
+    let approvers: Vec<&str> = completed
+        .approvals
+        .iter()
+        .map(|approval| approval.trustee_id.as_str())
+        .collect();
+    let event = AuditEvent::new(
+        AuditKind::SocialRecoveryCompleted,
+        Some(completed.owner_id.clone()),
+        format!("Social recovery approved by {}", approvers.join(", ")),
+    );
+    if let Err(e) = audit.record(event).await {
+        eprintln!("Failed to record audit event: {}", e);
+    }
+
+    match encode_recovery_jwt(&keys, &owner_id) {
+        Ok((token, claims)) => HttpResponse::Ok().json(json!({
+            "recovery_token": token,
+            "expires_at": claims.exp,
+            "user_id": completed.owner_id,
+            "user_name": completed.owner_name,
+        })),
+        Err(err) => HttpResponse::InternalServerError()
+            .json(json!({ "error": format!("Error generating token: {}", err) })),
+    }
+}
@@ -1,28 +1,54 @@
 use actix_files::NamedFile;
 use actix_web::{
+    http::Method,
     middleware::Logger,
     web::{self, Data, JsonConfig},
     {get, App, HttpRequest, HttpResponse, HttpServer, Responder},
 };
-use db::{init, init_user_db, user_crud::UserRepository};
+use db::{
+    init, init_audit_db, init_ceremony_db, init_social_recovery_db, init_token_db, init_user_db,
+    social_recovery_crud::SocialRecoveryRepository, user_crud::UserRepository,
+};
 use dotenv::dotenv;
+use handler::middleware::auth_middleware::CheckAuth;
+use handler::middleware::cors::{CorsPolicies, PublicRead};
 use handler::poll::poll_results;
-// use handler::middleware::auth_middleware::CheckAuth;
-use std::env;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+mod config;
 mod db;
 mod handler;
 mod models;
+mod tally;
 
-use crate::db::{config::DbConfig, poll_crud::PollRepository};
+use crate::config::AppConfig;
+use crate::db::{
+    audit_crud::AuditRepository, ceremony_crud::CeremonyStateRepository, poll_crud::PollRepository,
+    token_crud::TokenRepository,
+};
 use crate::handler::{
-    auth::{finish_authentication, finish_register, start_authentication, start_register},
-    poll::{add_polls, cast_vote, close_poll, delete_poll, fetch_polls, reset_vote},
+    admin::set_user_roles,
+    auth::{
+        finish_authentication, finish_discoverable_authentication, finish_register,
+        start_authentication, start_discoverable_authentication, start_register,
+    },
+    passkey::{
+        finish_add_passkey, list_passkeys, rename_passkey, revoke_passkey, start_add_passkey,
+    },
+    poll::{
+        activate_poll, add_polls, archive_poll, cast_vote, change_vote, close_poll, delete_poll,
+        expire_poll, fetch_polls, reset_vote, retract_vote, schedule_poll, update_poll,
+    },
+    recovery::{recover_account, regenerate_recovery_codes},
+    session::{jwks, logout, logout_all, refresh_session},
+    social_recovery::{
+        approve_recovery, collect_shares, complete_social_recovery, list_recovery_requests,
+        nominate_trustees, remove_trustees, start_social_recovery,
+    },
 };
-use crate::models::{auth_state::AuthenticationState, reg_state::RegistrationState};
-use actix_cors::Cors;
-use webauthn_rs::prelude::*; // Import the CORS middlewar
+use crate::models::jwt::JwtKeys;
+use webauthn_rs::prelude::*;
 
 #[get("/")]
 pub async fn root_handler(req: HttpRequest) -> impl Responder {
@@ -38,80 +64,239 @@ pub async fn auth_handler() -> impl Responder {
     HttpResponse::Ok().json("1.")
 }
 
+/// Prints a startup error readably and exits, rather than panicking or printing `Debug` output.
+fn exit_on_error<T, E: std::fmt::Display>(context: &str, result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}: {}", context, e);
+        std::process::exit(1)
+    })
+}
+
+/// Poll reads anyone may make without a token, from any page
+const PUBLIC_POLL_READS: [&str; 2] = ["/api/polls/{poll_id}", "/api/polls/{poll_id}/results"];
+
+/// Lets the public poll reads through `CheckAuth` while still attaching claims when sent.
+fn poll_auth() -> CheckAuth {
+    PUBLIC_POLL_READS
+        .iter()
+        .fold(CheckAuth::new(), |auth, path| {
+            auth.allow_public(Method::GET, path)
+        })
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    dotenv().ok();
+    let config = exit_on_error("Failed to load configuration", AppConfig::load());
+
     if std::env::var_os("RUST_LOG").is_none() {
-        std::env::set_var("RUST_LOG", "info");
+        std::env::set_var("RUST_LOG", &config.server.log_level);
     }
 
     // Initialize env-logger
     env_logger::init();
-    dotenv().ok();
 
     //webauthn setup
-    let rp_id = "localhost";
-    let rp_origin = Url::parse("http://localhost:3000").expect("Invalid URL");
-    let builder = WebauthnBuilder::new(rp_id, &rp_origin).expect("Invalid configuration");
-
-    let webauthn = Data::new(builder.build().expect("Invalid configuration"));
-
-    let reg_state_storage = Data::new(RegistrationState::new());
-    let auth_state_storeage = Data::new(AuthenticationState::new());
-    let config = DbConfig::new(
-        "mongodb",
-        env::var("DATABASE_URI")
-            .unwrap_or_else(|_| "mongodb://localhost:27017/?directConnection=true".to_string()),
-        "rustest",
+    let origins: Vec<Url> = config
+        .webauthn
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| Url::parse(origin).ok())
+        .collect();
+    let mut builder = exit_on_error(
+        "Invalid WebAuthn configuration",
+        WebauthnBuilder::new(&config.webauthn.rp_id, &origins[0]),
+    );
+    for origin in &origins[1..] {
+        builder = builder.append_allowed_origin(origin);
+    }
+    if let Some(rp_name) = &config.webauthn.rp_name {
+        builder = builder.rp_name(rp_name);
+    }
+    let webauthn = Data::new(exit_on_error(
+        "Invalid WebAuthn configuration",
+        builder.build(),
+    ));
+
+    // Refuse to start without usable token signing keys
+    let jwt_keys = Data::new(exit_on_error(
+        "Failed to load JWT keys",
+        JwtKeys::from_config(&config.jwt).map_err(|e| format!("{:#}", e)),
+    ));
+
+    let tls = config
+        .server
+        .tls
+        .as_ref()
+        .map(|tls| exit_on_error("Invalid TLS configuration", tls.server_config()));
+
+    let db_config = config.database.clone();
+    let poll_repo = exit_on_error(
+        "Failed to initialise poll storage",
+        init(db_config.clone()).await,
+    );
+    let user_repo = exit_on_error(
+        "Failed to create user indexes; are there users with duplicate names?",
+        init_user_db(db_config.clone()).await,
     );
+    let audit_repo = init_audit_db(db_config.clone()).await;
+    let token_repo = init_token_db(db_config.clone()).await;
+    let social_recovery_repo = init_social_recovery_db(db_config.clone()).await;
 
-    let poll_repo = init(config.clone()).await;
-    let user_repo = init_user_db(config).await;
+    // "memory" keeps ceremonies in this process; "mongodb" shares them between replicas
+    // Pending passkey ceremonies expire after `ttl_secs` and are swept periodically
+    let ceremony_store_type = config.ceremony_store();
+    log::info!("Keeping passkey ceremonies in {}", ceremony_store_type);
+    let ceremony_store = init_ceremony_db(
+        ceremony_store_type,
+        db_config,
+        Duration::from_secs(config.ceremonies.ttl_secs),
+    )
+    .await;
+    let sweep_interval = config.ceremonies.sweep_secs;
+
+    let ceremony_sweep = ceremony_store.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(sweep_interval));
+        loop {
+            interval.tick().await;
+            match ceremony_sweep.sweep_expired().await {
+                Ok(0) => {}
+                Ok(removed) => log::info!("Swept {} expired passkey ceremonies", removed),
+                Err(e) => log::error!("Failed to sweep passkey ceremonies: {}", e),
+            }
+        }
+    });
+    let ceremony_data: Data<dyn CeremonyStateRepository> = Data::from(ceremony_store);
 
     let store_arc: Arc<dyn PollRepository> = Arc::new(poll_repo);
+
+    // Scheduled polls open and polls past their deadline expire; voting already goes by the
+    // clock before the sweep catches up
+    let status_sweep = store_arc.clone();
+    let status_interval = config.polls.status_sweep_secs;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(status_interval));
+        loop {
+            interval.tick().await;
+            let now = chrono::Utc::now();
+            match status_sweep.open_due(now).await {
+                Ok(0) => {}
+                Ok(opened) => log::info!("Opened {} scheduled polls", opened),
+                Err(e) => log::error!("Failed to open scheduled polls: {}", e),
+            }
+            match status_sweep.expire_due(now).await {
+                Ok(0) => {}
+                Ok(expired) => log::info!("Marked {} polls as expired", expired),
+                Err(e) => log::error!("Failed to expire polls: {}", e),
+            }
+        }
+    });
     let store_data: Data<dyn PollRepository> = Data::from(store_arc);
 
     let user_store: Arc<dyn UserRepository> = Arc::new(user_repo);
     let user_data: Data<dyn UserRepository> = Data::from(user_store);
-    let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
 
-    HttpServer::new(move || {
+    let audit_store: Arc<dyn AuditRepository> = Arc::new(audit_repo);
+    let audit_data: Data<dyn AuditRepository> = Data::from(audit_store);
+
+    let token_store: Arc<dyn TokenRepository> = Arc::new(token_repo);
+    let token_data: Data<dyn TokenRepository> = Data::from(token_store);
+
+    let social_recovery_store: Arc<dyn SocialRecoveryRepository> = Arc::new(social_recovery_repo);
+    let social_recovery_data: Data<dyn SocialRecoveryRepository> =
+        Data::from(social_recovery_store);
+    let bind_address = (config.server.bind_address.clone(), config.server.port);
+    let cors = CorsPolicies::new(config.cors.clone());
+    let config_data = Data::new(config);
+
+    let server = HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
             .app_data(store_data.clone())
             .app_data(user_data.clone())
-            .app_data(reg_state_storage.clone())
-            .app_data(auth_state_storeage.clone())
+            .app_data(audit_data.clone())
+            .app_data(token_data.clone())
+            .app_data(social_recovery_data.clone())
+            .app_data(ceremony_data.clone())
             .app_data(JsonConfig::default())
             .app_data(webauthn.clone())
+            .app_data(jwt_keys.clone())
+            .app_data(config_data.clone())
             .service(root_handler)
             .service(auth_handler)
+            .service(
+                web::scope("/.well-known")
+                    .wrap(cors.public_read())
+                    .service(jwks),
+            )
+            // Public poll reads get the looser policy; the rest of /api falls through to the next scope
+            .service(
+                web::scope("api")
+                    .guard(PublicRead::new(&PUBLIC_POLL_READS))
+                    .wrap(poll_auth())
+                    .wrap(cors.public_read())
+                    .service(fetch_polls)
+                    .service(poll_results),
+            )
             .service(
                 web::scope("api/auth")
+                    .wrap(cors.strict())
                     .service(start_register)
                     .service(finish_register)
                     .service(start_authentication)
-                    .service(finish_authentication),
+                    .service(finish_authentication)
+                    .service(start_discoverable_authentication)
+                    .service(finish_discoverable_authentication)
+                    .service(refresh_session)
+                    .service(logout)
+                    .service(logout_all)
+                    .service(recover_account)
+                    .service(regenerate_recovery_codes)
+                    .service(
+                        web::scope("social_recovery")
+                            .service(nominate_trustees)
+                            .service(remove_trustees)
+                            .service(start_social_recovery)
+                            .service(collect_shares)
+                            .service(list_recovery_requests)
+                            .service(approve_recovery)
+                            .service(complete_social_recovery),
+                    )
+                    .service(
+                        web::scope("passkeys")
+                            .wrap(CheckAuth::new())
+                            .service(start_add_passkey)
+                            .service(finish_add_passkey)
+                            .service(list_passkeys)
+                            .service(rename_passkey)
+                            .service(revoke_passkey),
+                    ),
             )
             .service(
                 web::scope("api")
-                    //.wrap(CheckAuth)
+                    .wrap(CheckAuth::new())
+                    .wrap(cors.strict())
+                    .service(web::scope("admin").service(set_user_roles))
                     .service(add_polls)
-                    .service(fetch_polls)
                     .service(delete_poll)
                     .service(cast_vote)
+                    .service(change_vote)
+                    .service(retract_vote)
+                    .service(update_poll)
+                    .service(schedule_poll)
+                    .service(activate_poll)
                     .service(close_poll)
-                    .service(reset_vote)
-                    .service(poll_results),
+                    .service(expire_poll)
+                    .service(archive_poll)
+                    .service(reset_vote),
             )
-            .wrap(
-                Cors::default() // Configure CORS to allow all origins
-                    .allow_any_origin() // Allows all origins
-                    .allow_any_method() // Allows any HTTP method
-                    .allow_any_header()
-                    .supports_credentials(), // Allows any header
-            )
-    })
-    .bind(("0.0.0.0", port.parse::<u16>().unwrap()))?
+    });
+
+    match tls {
+        Some(tls) => server.bind_rustls_021(bind_address, tls)?,
+        None => server.bind(bind_address)?,
+    }
     .run()
     .await
 }
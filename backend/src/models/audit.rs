@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    /// An authenticator presented a signature counter at or below the stored one
+    CounterRegression,
+    /// A refresh token was presented after it had already been rotated or revoked
+    RefreshTokenReuse,
+    /// An admin changed a user's roles
+    RolesChanged,
+    /// A recovery code was exchanged for a passkey enrollment token
+    RecoveryCodeUsed,
+    /// Trustees' shares were combined to let an owner enroll a passkey
+    SocialRecoveryCompleted,
+}
+
+/// Security-relevant event kept for later review.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEvent {
+    pub kind: AuditKind,
+    pub user_id: Option<String>,
+    pub detail: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditKind, user_id: Option<String>, detail: String) -> Self {
+        AuditEvent {
+            kind,
+            user_id,
+            detail,
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::*;
+
+/// A passkey registration that has been started but not yet finished.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegistrationCeremony {
+    pub user_name: String,
+    pub user_id: Uuid,
+    pub state: PasskeyRegistration,
+    /// Set when a signed-in user is adding a passkey rather than creating an account
+    #[serde(default)]
+    pub existing_account: bool,
+    pub nickname: Option<String>,
+}
+
+/// A passkey authentication that has been started but not yet finished.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuthenticationCeremony {
+    pub user_id: Uuid,
+    pub state: PasskeyAuthentication,
+}
+
+/// A usernameless authentication, where the browser picks the account.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiscoverableCeremony {
+    pub state: DiscoverableAuthentication,
+}
+
+/// Challenge returned by the `start_*` endpoints, tagged with the ceremony it belongs to.
+/// The challenge fields are flattened so clients can keep reading `publicKey` directly.
+#[derive(Debug, Serialize)]
+pub struct CeremonyResponse<T> {
+    pub ceremony_id: Uuid,
+    #[serde(flatten)]
+    pub challenge: T,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CeremonyQuery {
+    pub ceremony_id: Uuid,
+}
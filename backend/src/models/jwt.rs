@@ -1,18 +1,72 @@
+use std::collections::HashMap;
 use std::future;
+use std::path::Path;
 
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::error::InternalError;
+use actix_web::{FromRequest, HttpMessage, HttpResponse};
+use anyhow::{anyhow, bail, Context};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
 use chrono::{Duration, Utc};
-use dotenv::dotenv;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData,
+    Validation,
+};
 use serde::{Deserialize, Serialize};
-use std::env;
+use serde_json::json;
 use uuid::Uuid;
 
+use crate::config::{JwtConfig, DEFAULT_JWT_SECRET};
+use crate::db::token_crud::TokenRepository;
+use crate::models::user::Role;
+
+/// `kid` carried by HS256 tokens, which are only issued in dev mode or with a custom `SECRET`
+const HMAC_KID: &str = "hmac";
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo; the raw 32-byte key follows it
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// What a token may be used for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// A regular session token issued at login or refresh
+    #[default]
+    Session,
+    /// A short-lived token from a recovery code that can only enroll a passkey
+    Recovery,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Claims {
     pub exp: usize,
     pub iat: usize,
     pub uuid: Uuid,
+    /// Unique ID of this token, used to revoke it before it expires
+    pub jti: Uuid,
+    /// Refresh token family this token was issued with
+    pub fid: Uuid,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    #[serde(default)]
+    pub scope: TokenScope,
+}
+
+impl Claims {
+    pub fn is_admin(&self) -> bool {
+        self.roles.contains(&Role::Admin)
+    }
+
+    /// Whether the caller may change a resource owned by `owner_id`: its owner or an admin.
+    pub fn can_manage(&self, owner_id: &str) -> bool {
+        self.uuid.to_string() == owner_id || self.is_admin()
+    }
 }
 
 impl FromRequest for Claims {
@@ -25,39 +79,275 @@ impl FromRequest for Claims {
         _payload: &mut actix_web::dev::Payload,
     ) -> std::future::Ready<Result<Claims, actix_web::Error>> {
         match req.extensions().get::<Claims>() {
-            Some(claim) => future::ready(Ok(claim.clone())),
-            None => future::ready(Err(actix_web::error::ErrorBadRequest("Bad Claims"))),
+            Some(claim) if claim.scope == TokenScope::Session => future::ready(Ok(claim.clone())),
+            Some(_) => future::ready(Err(forbidden("This token can only enroll a passkey"))),
+            None => future::ready(Err(actix_web::error::ErrorUnauthorized("Unauthorized"))),
         }
     }
 }
 
-pub fn encode_jwt(uuid: &Uuid) -> Result<String, jsonwebtoken::errors::Error> {
-    dotenv().ok();
+/// Claims of a caller allowed to enroll a passkey: a signed in user, or one holding a
+/// recovery token.
+pub struct EnrollmentClaims(pub Claims);
+
+impl FromRequest for EnrollmentClaims {
+    type Error = actix_web::Error;
+
+    type Future = future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        match req.extensions().get::<Claims>() {
+            Some(claim) => future::ready(Ok(EnrollmentClaims(claim.clone()))),
+            None => future::ready(Err(actix_web::error::ErrorUnauthorized("Unauthorized"))),
+        }
+    }
+}
+
+fn forbidden(message: &str) -> actix_web::Error {
+    InternalError::from_response(
+        "Forbidden",
+        HttpResponse::Forbidden().json(json!({ "error": message })),
+    )
+    .into()
+}
+
+/// Claims of a caller holding the admin role; anyone else is refused with 403.
+pub struct AdminClaims(pub Claims);
+
+impl FromRequest for AdminClaims {
+    type Error = actix_web::Error;
+
+    type Future = future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        match req.extensions().get::<Claims>() {
+            Some(claim) if claim.is_admin() && claim.scope == TokenScope::Session => {
+                future::ready(Ok(AdminClaims(claim.clone())))
+            }
+            Some(_) => future::ready(Err(forbidden("Admin role required"))),
+            None => future::ready(Err(actix_web::error::ErrorUnauthorized("Unauthorized"))),
+        }
+    }
+}
+
+/// Keys used to sign and verify access tokens, and the lifetimes of the tokens they issue.
+///
+/// With `jwt.keys_dir` set, every `<kid>.pub.pem` (Ed25519 SPKI) in the directory verifies
+/// tokens and `<kid>.key.pem` (Ed25519 PKCS#8) for `jwt.signing_kid` signs new ones, so a key
+/// can be rotated by adding a pair, switching the signing kid and dropping the old public key
+/// once its tokens have expired. Without a key directory tokens fall back to HS256 with
+/// `jwt.secret`; config validation only lets the built-in default through in dev mode.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    signing_kid: String,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, DecodingKey>,
+    jwks: JwkSet,
+    lifetimes: TokenLifetimes,
+}
+
+struct TokenLifetimes {
+    access: Duration,
+    refresh: Duration,
+    recovery: Duration,
+}
+
+impl JwtKeys {
+    pub fn from_config(config: &JwtConfig) -> anyhow::Result<Self> {
+        let lifetimes = TokenLifetimes {
+            access: Duration::minutes(config.access_token_minutes),
+            refresh: Duration::days(config.refresh_token_days),
+            recovery: Duration::minutes(config.recovery_token_minutes),
+        };
+        match (&config.keys_dir, &config.signing_kid) {
+            (Some(dir), Some(signing_kid)) => Self::from_dir(dir, signing_kid, lifetimes),
+            (Some(_), None) => bail!("jwt.signing_kid must be set when jwt.keys_dir is"),
+            (None, _) => Ok(Self::from_secret(
+                config.secret.as_deref().unwrap_or(DEFAULT_JWT_SECRET),
+                lifetimes,
+            )),
+        }
+    }
+
+    fn from_secret(secret: &str, lifetimes: TokenLifetimes) -> Self {
+        JwtKeys {
+            algorithm: Algorithm::HS256,
+            signing_kid: HMAC_KID.to_string(),
+            encoding_key: EncodingKey::from_secret(secret.as_ref()),
+            decoding_keys: HashMap::from([(
+                HMAC_KID.to_string(),
+                DecodingKey::from_secret(secret.as_ref()),
+            )]),
+            // A shared secret is never published
+            jwks: JwkSet { keys: vec![] },
+            lifetimes,
+        }
+    }
+
+    fn from_dir(dir: &Path, signing_kid: &str, lifetimes: TokenLifetimes) -> anyhow::Result<Self> {
+        let mut decoding_keys = HashMap::new();
+        let mut jwks = JwkSet { keys: vec![] };
+
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Cannot read JWT key directory {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let Some(kid) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".pub.pem"))
+            else {
+                continue;
+            };
+
+            let pem = std::fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read {}", path.display()))?;
+            let x = ed25519_public_key(&pem)
+                .with_context(|| format!("Invalid Ed25519 public key in {}", path.display()))?;
+            decoding_keys.insert(kid.to_string(), DecodingKey::from_ed_components(&x)?);
+            jwks.keys.push(Jwk {
+                common: CommonParameters {
+                    public_key_use: Some(PublicKeyUse::Signature),
+                    key_algorithm: Some(KeyAlgorithm::EdDSA),
+                    key_id: Some(kid.to_string()),
+                    ..Default::default()
+                },
+                algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x,
+                }),
+            });
+        }
+
+        if !decoding_keys.contains_key(signing_kid) {
+            bail!(
+                "No public key {}.pub.pem for signing key {:?} in {}",
+                signing_kid,
+                signing_kid,
+                dir.display()
+            );
+        }
+        let private_path = dir.join(format!("{}.key.pem", signing_kid));
+        let private_pem = std::fs::read(&private_path)
+            .with_context(|| format!("Cannot read {}", private_path.display()))?;
+        let encoding_key = EncodingKey::from_ed_pem(&private_pem).with_context(|| {
+            format!("Invalid Ed25519 private key in {}", private_path.display())
+        })?;
+
+        Ok(JwtKeys {
+            algorithm: Algorithm::EdDSA,
+            signing_kid: signing_kid.to_string(),
+            encoding_key,
+            decoding_keys,
+            jwks,
+            lifetimes,
+        })
+    }
+
+    /// Public verification keys, as served from `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    pub fn refresh_token_lifetime(&self) -> Duration {
+        self.lifetimes.refresh
+    }
+}
+
+/// Extracts the base64url-encoded raw key from an Ed25519 SPKI PEM.
+fn ed25519_public_key(pem: &str) -> anyhow::Result<String> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let der = STANDARD.decode(body.trim())?;
+    let raw = der
+        .strip_prefix(&ED25519_SPKI_PREFIX[..])
+        .filter(|raw| raw.len() == 32)
+        .ok_or_else(|| anyhow!("not an Ed25519 SubjectPublicKeyInfo"))?;
+    Ok(URL_SAFE_NO_PAD.encode(raw))
+}
+
+pub fn encode_jwt(
+    keys: &JwtKeys,
+    uuid: &Uuid,
+    family_id: &Uuid,
+    roles: Vec<Role>,
+) -> Result<(String, Claims), jsonwebtoken::errors::Error> {
     let now = Utc::now();
-    let expire = Duration::hours(24);
+    let expire = keys.lifetimes.access;
 
     let claims = Claims {
         exp: (now + expire).timestamp() as usize,
         iat: now.timestamp() as usize,
         uuid: *uuid,
+        jti: Uuid::new_v4(),
+        fid: *family_id,
+        roles,
+        scope: TokenScope::Session,
     };
 
-    let secret = env::var("SECRET").unwrap_or("notsosecuresecret".to_string());
+    sign(keys, claims)
+}
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
+/// Issues a recovery token for `uuid`. It carries no roles and belongs to no refresh token
+/// family, so it can neither be refreshed nor used beyond passkey enrollment.
+pub fn encode_recovery_jwt(
+    keys: &JwtKeys,
+    uuid: &Uuid,
+) -> Result<(String, Claims), jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+
+    let claims = Claims {
+        exp: (now + keys.lifetimes.recovery).timestamp() as usize,
+        iat: now.timestamp() as usize,
+        uuid: *uuid,
+        jti: Uuid::new_v4(),
+        fid: Uuid::new_v4(),
+        roles: vec![],
+        scope: TokenScope::Recovery,
+    };
+
+    sign(keys, claims)
 }
 
-pub fn decode_jwt(jwt: String) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
-    let secret = env::var("SECRET").unwrap_or("notsosecuresecret".to_string());
-    let claim_data: Result<TokenData<Claims>, jsonwebtoken::errors::Error> = decode(
-        &jwt,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    );
+fn sign(keys: &JwtKeys, claims: Claims) -> Result<(String, Claims), jsonwebtoken::errors::Error> {
+    let mut header = Header::new(keys.algorithm);
+    header.kid = Some(keys.signing_kid.clone());
 
-    claim_data
+    let token = encode(&header, &claims, &keys.encoding_key)?;
+    Ok((token, claims))
+}
+
+/// Decodes and validates the token against the key named by its `kid`, rejecting it if its
+/// `jti` has been revoked.
+pub async fn decode_jwt(
+    jwt: String,
+    keys: &JwtKeys,
+    revoked: &dyn TokenRepository,
+) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
+    let header = decode_header(&jwt)?;
+    let key = header
+        .kid
+        .as_ref()
+        .and_then(|kid| keys.decoding_keys.get(kid))
+        .ok_or(ErrorKind::InvalidToken)?;
+    let claim_data: TokenData<Claims> = decode(&jwt, key, &Validation::new(keys.algorithm))?;
+
+    match revoked.is_revoked(claim_data.claims.jti.to_string()).await {
+        Ok(false) => Ok(claim_data),
+        Ok(true) => Err(ErrorKind::InvalidToken.into()),
+        Err(e) => {
+            // Fail closed: a token that cannot be checked is not accepted
+            eprintln!("Failed to check token revocation: {}", e);
+            Err(ErrorKind::InvalidToken.into())
+        }
+    }
 }
@@ -1,5 +1,8 @@
-pub mod auth_state;
+pub mod audit;
+pub mod ceremony;
 pub mod jwt;
 pub mod poll;
-pub mod reg_state;
+pub mod recovery;
+pub mod social_recovery;
+pub mod token;
 pub mod user;
@@ -1,15 +1,175 @@
 use chrono::{DateTime, Utc};
 use futures::Stream;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::pin::Pin;
 use std::task::Context;
 use tokio::sync::mpsc;
 
+use crate::tally::instant_runoff::{instant_runoff, Runoff};
+use crate::tally::schulze::{schulze, Schulze};
+use crate::tally::score::{score_tally, OptionScore, ScoreTally};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PollOption {
     pub option_id: i64,
     pub text: String,
+    /// How many voters chose the option
     pub votes: i32,
+    /// The summed weight of those voters; only kept in weighted polls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<i64>,
+}
+
+/// One voter's ballot, stored as cast for polls that tally from ballots rather than counters.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Ballot {
+    pub poll_id: i64,
+    pub user_id: String,
+    /// Option IDs, most preferred first; empty on score ballots
+    #[serde(default)]
+    pub ranking: Vec<i64>,
+    /// A score for every option, on score ballots only
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scores: Vec<OptionScore>,
+    pub cast_at: DateTime<Utc>,
+}
+
+impl Ballot {
+    pub fn new(poll_id: i64, user_id: &str, choice: &BallotChoice, cast_at: DateTime<Utc>) -> Self {
+        let (ranking, scores) = match choice {
+            BallotChoice::Options(option_ids) => (option_ids.clone(), Vec::new()),
+            BallotChoice::Scores(scores) => (Vec::new(), scores.clone()),
+        };
+        Ballot {
+            poll_id,
+            user_id: user_id.to_string(),
+            ranking,
+            scores,
+            cast_at,
+        }
+    }
+}
+
+/// What a voter puts on a ballot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BallotChoice {
+    /// The options selected, in order of preference on ranked ballots
+    Options(Vec<i64>),
+    /// A score for each option
+    Scores(Vec<OptionScore>),
+}
+
+impl BallotChoice {
+    /// The options the ballot names, in the order it names them.
+    pub fn option_ids(&self) -> Vec<i64> {
+        match self {
+            BallotChoice::Options(option_ids) => option_ids.clone(),
+            BallotChoice::Scores(scores) => scores.iter().map(|score| score.option_id).collect(),
+        }
+    }
+}
+
+/// Where a poll is in its life. Polls only move forward:
+/// draft → scheduled → active → closed or expired → archived.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PollStatus {
+    /// Being prepared by its owner
+    Draft,
+    /// Ready and waiting to open
+    Scheduled,
+    /// Accepting votes
+    Active,
+    /// Ended early by its owner
+    Closed,
+    /// Ended because its deadline passed
+    Expired,
+    /// Kept for the record and hidden from day-to-day use
+    Archived,
+}
+
+impl PollStatus {
+    pub const ALL: [PollStatus; 6] = [
+        PollStatus::Draft,
+        PollStatus::Scheduled,
+        PollStatus::Active,
+        PollStatus::Closed,
+        PollStatus::Expired,
+        PollStatus::Archived,
+    ];
+
+    /// Drafts and scheduled polls are only shown to their owner and admins.
+    pub fn is_published(self) -> bool {
+        !matches!(self, PollStatus::Draft | PollStatus::Scheduled)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PollStatus::Draft => "draft",
+            PollStatus::Scheduled => "scheduled",
+            PollStatus::Active => "active",
+            PollStatus::Closed => "closed",
+            PollStatus::Expired => "expired",
+            PollStatus::Archived => "archived",
+        }
+    }
+
+    /// The statuses a poll may move to `self` from.
+    pub fn allowed_from(self) -> &'static [PollStatus] {
+        match self {
+            PollStatus::Draft => &[],
+            PollStatus::Scheduled => &[PollStatus::Draft],
+            PollStatus::Active => &[PollStatus::Scheduled],
+            PollStatus::Closed | PollStatus::Expired => &[PollStatus::Active],
+            PollStatus::Archived => &[PollStatus::Closed, PollStatus::Expired],
+        }
+    }
+}
+
+impl fmt::Display for PollStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How ballots are filled in and counted.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VotingMethod {
+    /// Each ballot selects exactly one option
+    #[default]
+    SingleChoice,
+    /// Each ballot approves any number of options within the poll's selection limits
+    Approval,
+    /// Each ballot ranks options in order of preference, counted by instant runoff
+    RankedChoice,
+    /// Ranked ballots compared pairwise: the Condorcet winner if there is one, else by Schulze
+    Schulze,
+    /// Each ballot gives every option a score within the poll's range
+    Score,
+}
+
+impl VotingMethod {
+    /// Whether ballots are stored one by one and tallied from there, rather than added to
+    /// per-option counters as they arrive.
+    pub fn keeps_ballots(&self) -> bool {
+        matches!(
+            self,
+            VotingMethod::RankedChoice | VotingMethod::Schulze | VotingMethod::Score
+        )
+    }
+}
+
+/// Result of moving a poll to another status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionOutcome {
+    /// The poll is now in the new status
+    Done,
+    PollNotFound,
+    /// The poll's current status cannot move to the requested one
+    Illegal(PollStatus),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -19,10 +179,694 @@ pub struct Poll {
     pub creator: String,
     pub description: String,
     pub created_at: DateTime<Utc>,
+    #[serde(default, with = "stored_date")]
     pub expiration_date: Option<DateTime<Utc>>,
-    pub status: String, // Active, expired, closed
+    pub status: PollStatus,
     pub options: Vec<PollOption>,
     pub users_voted: Vec<String>,
+    /// Whether voters may move or withdraw their vote while the poll is open
+    #[serde(default = "default_true")]
+    pub allow_vote_changes: bool,
+    /// When a scheduled poll opens for votes
+    #[serde(default, with = "stored_date")]
+    pub opens_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub voting_method: VotingMethod,
+    /// Fewest options an approval or ranked ballot may select
+    #[serde(default = "default_min_selections")]
+    pub min_selections: u32,
+    /// Most options an approval or ranked ballot may select; every option when unset
+    #[serde(default)]
+    pub max_selections: Option<u32>,
+    /// Lowest score a score ballot may give
+    #[serde(default)]
+    pub min_score: u32,
+    /// Highest score a score ballot may give
+    #[serde(default = "default_max_score")]
+    pub max_score: u32,
+    /// Whether a score poll's results include a STAR runoff between the top two options
+    #[serde(default)]
+    pub star_runoff: bool,
+    /// Who may vote in a weighted poll, by user ID, and how much each vote counts; anyone may
+    /// vote with a weight of 1 when unset
+    #[serde(default)]
+    pub voter_roll: Option<BTreeMap<String, u32>>,
+}
+
+/// Keeps a poll's opening time and deadline as BSON dates in MongoDB, so queries can compare
+/// them, while JSON still gets RFC 3339 strings. Reads either form, as polls stored before
+/// the change hold strings.
+mod stored_date {
+    use chrono::{DateTime, Utc};
+    use mongodb::bson::{self, Bson};
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) if !serializer.is_human_readable() => {
+                bson::DateTime::from_millis(time.timestamp_millis()).serialize(serializer)
+            }
+            time => time.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Bson::deserialize(deserializer)? {
+            Bson::Null => Ok(None),
+            Bson::DateTime(time) => DateTime::from_timestamp_millis(time.timestamp_millis())
+                .map(Some)
+                .ok_or_else(|| D::Error::custom("date out of range")),
+            Bson::String(time) => DateTime::parse_from_rfc3339(&time)
+                .map(|time| Some(time.with_timezone(&Utc)))
+                .map_err(D::Error::custom),
+            other => Err(D::Error::custom(format!(
+                "expected a date, found {:?}",
+                other.element_type()
+            ))),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_min_selections() -> u32 {
+    1
+}
+
+fn default_max_score() -> u32 {
+    5
+}
+
+impl Poll {
+    /// Whether the poll had a deadline and it has passed by `now`.
+    pub fn is_past_deadline(&self, now: DateTime<Utc>) -> bool {
+        self.expiration_date.is_some_and(|deadline| deadline <= now)
+    }
+
+    /// The fewest and most options one ballot may select.
+    pub fn selection_limits(&self) -> (usize, usize) {
+        match self.voting_method {
+            VotingMethod::SingleChoice => (1, 1),
+            VotingMethod::Score => (self.options.len(), self.options.len()),
+            VotingMethod::Approval | VotingMethod::RankedChoice | VotingMethod::Schulze => (
+                self.min_selections as usize,
+                self.max_selections
+                    .map_or(self.options.len(), |max| max as usize),
+            ),
+        }
+    }
+
+    /// Checks a ballot selects known options, each once, within the poll's limits.
+    pub fn check_selection(&self, option_ids: &[i64]) -> Result<(), String> {
+        for (i, option_id) in option_ids.iter().enumerate() {
+            if option_ids[..i].contains(option_id) {
+                return Err(format!("option {} is selected more than once", option_id));
+            }
+            if !self
+                .options
+                .iter()
+                .any(|option| option.option_id == *option_id)
+            {
+                return Err(format!("this poll has no option {}", option_id));
+            }
+        }
+        let (min, max) = self.selection_limits();
+        if option_ids.len() < min || option_ids.len() > max {
+            return Err(if min == max {
+                format!("select exactly {} option(s)", min)
+            } else {
+                format!("select between {} and {} options", min, max)
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks a score ballot rates every option once, within the poll's range.
+    pub fn check_scores(&self, scores: &[OptionScore]) -> Result<(), String> {
+        let option_ids: Vec<i64> = scores.iter().map(|score| score.option_id).collect();
+        self.check_selection(&option_ids)
+            .map_err(|_| "score every option exactly once".to_string())?;
+        match scores
+            .iter()
+            .find(|score| !(self.min_score..=self.max_score).contains(&score.score))
+        {
+            Some(score) => Err(format!(
+                "the score for option {} must be between {} and {}",
+                score.option_id, self.min_score, self.max_score
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// How much `user_id`'s vote counts, or None if the poll has a voter roll without them.
+    pub fn weight_of(&self, user_id: &str) -> Option<u32> {
+        match &self.voter_roll {
+            Some(roll) => roll.get(user_id).copied(),
+            None => Some(1),
+        }
+    }
+
+    /// Checks a ballot is the kind this poll takes and fills it in correctly.
+    pub fn check_ballot(&self, choice: &BallotChoice) -> Result<(), String> {
+        match (self.voting_method, choice) {
+            (VotingMethod::Score, BallotChoice::Scores(scores)) => self.check_scores(scores),
+            (VotingMethod::Score, BallotChoice::Options(_)) => {
+                Err("this poll takes a score for every option".to_string())
+            }
+            (_, BallotChoice::Options(option_ids)) => self.check_selection(option_ids),
+            (_, BallotChoice::Scores(_)) => Err("this poll does not take scores".to_string()),
+        }
+    }
+
+    /// Whether the poll is scheduled and its opening time has come by `now`.
+    pub fn is_due_to_open(&self, now: DateTime<Utc>) -> bool {
+        self.status == PollStatus::Scheduled && self.opens_at.is_some_and(|opens| opens <= now)
+    }
+}
+
+/// Who is listing polls, which decides whether unpublished polls are included.
+#[derive(Debug, Clone)]
+pub enum Viewer {
+    Anonymous,
+    /// Sees their own drafts and scheduled polls
+    Member(String),
+    /// Sees every poll
+    Admin,
+}
+
+/// Most options a poll may offer
+pub const MAX_POLL_OPTIONS: usize = 20;
+/// Longest title, in characters
+pub const MAX_TITLE_LEN: usize = 200;
+
+/// What a client may choose when creating a poll; the ID, creator, timestamps and counts are
+/// always set by the server.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CreatePollRequest {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Option texts, in display order
+    pub options: Vec<String>,
+    pub expiration_date: Option<DateTime<Utc>>,
+    #[serde(default = "default_true")]
+    pub allow_vote_changes: bool,
+    /// Start as a draft to finish later, rather than opening for votes straight away
+    #[serde(default)]
+    pub draft: bool,
+    /// Schedule the poll to open at this time instead of straight away
+    pub opens_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub voting_method: VotingMethod,
+    /// Approval and ranked polls only; defaults to 1
+    pub min_selections: Option<u32>,
+    /// Approval and ranked polls only; defaults to every option
+    pub max_selections: Option<u32>,
+    /// Score polls only; defaults to 0
+    pub min_score: Option<u32>,
+    /// Score polls only; defaults to 5
+    pub max_score: Option<u32>,
+    /// Score polls only
+    #[serde(default)]
+    pub star_runoff: bool,
+    /// User IDs allowed to vote, with the weight of each one's vote
+    pub voter_roll: Option<BTreeMap<String, u32>>,
+}
+
+/// Changes the owner may make after creating a poll; absent fields are left alone. The text
+/// and options can only change while the poll is a draft.
+#[derive(Debug, Deserialize, Clone)]
+pub struct UpdatePollRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub options: Option<Vec<String>>,
+    pub voting_method: Option<VotingMethod>,
+    pub min_selections: Option<u32>,
+    pub max_selections: Option<u32>,
+    pub min_score: Option<u32>,
+    pub max_score: Option<u32>,
+    pub star_runoff: Option<bool>,
+    pub voter_roll: Option<BTreeMap<String, u32>>,
+    pub allow_vote_changes: Option<bool>,
+    /// A new opening time, for a draft or scheduled poll
+    pub opens_at: Option<DateTime<Utc>>,
+    /// A new deadline, earlier or later than the current one
+    pub expiration_date: Option<DateTime<Utc>>,
+}
+
+/// Body of a request to schedule a draft.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SchedulePollRequest {
+    pub opens_at: DateTime<Utc>,
+}
+
+/// One rejected field of a request, reported back with a 422.
+#[derive(Debug, Serialize, Clone)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        FieldError {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+/// Trims the title and checks it is present and not too long.
+fn validate_title(title: &mut String, errors: &mut Vec<FieldError>) {
+    *title = title.trim().to_string();
+    if title.is_empty() {
+        errors.push(FieldError::new("title", "must not be empty"));
+    } else if title.chars().count() > MAX_TITLE_LEN {
+        errors.push(FieldError::new(
+            "title",
+            format!("must be at most {} characters", MAX_TITLE_LEN),
+        ));
+    }
+}
+
+/// Trims the options and checks there are enough of them and that they differ.
+fn validate_options(options: &mut Vec<String>, errors: &mut Vec<FieldError>) {
+    *options = options
+        .iter()
+        .map(|option| option.trim().to_string())
+        .collect();
+    if options.len() < 2 || options.len() > MAX_POLL_OPTIONS {
+        errors.push(FieldError::new(
+            "options",
+            format!("must have between 2 and {} options", MAX_POLL_OPTIONS),
+        ));
+    }
+    if options.iter().any(String::is_empty) {
+        errors.push(FieldError::new("options", "must not be empty"));
+    }
+    let mut seen = std::collections::HashSet::new();
+    if !options
+        .iter()
+        .all(|option| seen.insert(option.to_lowercase()))
+    {
+        errors.push(FieldError::new("options", "must be unique"));
+    }
+}
+
+fn validate_future(
+    field: &'static str,
+    time: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    errors: &mut Vec<FieldError>,
+) {
+    if time.is_some_and(|time| time <= now) {
+        errors.push(FieldError::new(field, "must be in the future"));
+    }
+}
+
+/// Highest `max_score` a score poll may use
+pub const MAX_SCORE: u32 = 100;
+
+/// Checks the score range and STAR runoff are only set on score polls, and that the range makes
+/// sense.
+pub fn validate_score_rules(
+    method: VotingMethod,
+    min: Option<u32>,
+    max: Option<u32>,
+    star_runoff: bool,
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    if method == VotingMethod::Score {
+        let min = min.unwrap_or_default();
+        let max = max.unwrap_or_else(default_max_score);
+        if max > MAX_SCORE {
+            errors.push(FieldError::new(
+                "max_score",
+                format!("must be at most {}", MAX_SCORE),
+            ));
+        }
+        if min >= max {
+            errors.push(FieldError::new("min_score", "must be below max_score"));
+        }
+    } else {
+        let fields = [
+            ("min_score", min.is_some()),
+            ("max_score", max.is_some()),
+            ("star_runoff", star_runoff),
+        ];
+        for (field, set) in fields {
+            if set {
+                errors.push(FieldError::new(field, "only applies to score polls"));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Largest voter roll a poll may have
+pub const MAX_VOTER_ROLL: usize = 10_000;
+
+/// Checks a voter roll is only set on a poll counted by its counters, and that every voter on it
+/// has a positive weight.
+pub fn validate_voter_roll(
+    method: VotingMethod,
+    roll: Option<&BTreeMap<String, u32>>,
+) -> Result<(), Vec<FieldError>> {
+    let Some(roll) = roll else {
+        return Ok(());
+    };
+    let message = if method.keeps_ballots() {
+        "only applies to single-choice and approval polls".to_string()
+    } else if roll.is_empty() || roll.len() > MAX_VOTER_ROLL {
+        format!("must list between 1 and {} voters", MAX_VOTER_ROLL)
+    } else {
+        match roll.iter().find(|(_, weight)| **weight == 0) {
+            Some((user_id, _)) => format!("the weight for {} must be at least 1", user_id),
+            None => return Ok(()),
+        }
+    };
+    Err(vec![FieldError::new("voter_roll", message)])
+}
+
+/// Checks the selection limits suit the voting method and the number of options.
+pub fn validate_selection_rules(
+    method: VotingMethod,
+    min: Option<u32>,
+    max: Option<u32>,
+    option_count: usize,
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    match method {
+        VotingMethod::Score => {
+            for (field, set) in [
+                ("min_selections", min.is_some()),
+                ("max_selections", max.is_some()),
+            ] {
+                if set {
+                    errors.push(FieldError::new(field, "does not apply to score polls"));
+                }
+            }
+        }
+        VotingMethod::SingleChoice => {
+            if min.is_some_and(|min| min != 1) {
+                errors.push(FieldError::new(
+                    "min_selections",
+                    "does not apply to single-choice polls",
+                ));
+            }
+            if max.is_some_and(|max| max != 1) {
+                errors.push(FieldError::new(
+                    "max_selections",
+                    "does not apply to single-choice polls",
+                ));
+            }
+        }
+        VotingMethod::Approval | VotingMethod::RankedChoice | VotingMethod::Schulze => {
+            let min = min.unwrap_or_else(default_min_selections);
+            if min == 0 || min as usize > option_count {
+                errors.push(FieldError::new(
+                    "min_selections",
+                    format!(
+                        "must be between 1 and the number of options ({})",
+                        option_count
+                    ),
+                ));
+            }
+            if let Some(max) = max {
+                if max < min || max as usize > option_count {
+                    errors.push(FieldError::new(
+                        "max_selections",
+                        "must be between min_selections and the number of options",
+                    ));
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks a poll would open before it closes.
+pub fn validate_window(
+    opens_at: Option<DateTime<Utc>>,
+    expiration_date: Option<DateTime<Utc>>,
+) -> Result<(), Vec<FieldError>> {
+    match (opens_at, expiration_date) {
+        (Some(opens_at), Some(expiration)) if opens_at >= expiration => Err(vec![FieldError::new(
+            "opens_at",
+            "must be before expiration_date",
+        )]),
+        _ => Ok(()),
+    }
+}
+
+fn into_options(texts: Vec<String>, weighted: bool) -> Vec<PollOption> {
+    texts
+        .into_iter()
+        .enumerate()
+        .map(|(option_id, text)| PollOption {
+            option_id: option_id as i64,
+            text,
+            votes: 0,
+            weight: weighted.then_some(0),
+        })
+        .collect()
+}
+
+impl CreatePollRequest {
+    /// Trims the text fields and checks every rule, returning all the problems found.
+    pub fn validate(mut self, now: DateTime<Utc>) -> Result<Self, Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        validate_title(&mut self.title, &mut errors);
+        self.description = self.description.trim().to_string();
+        validate_options(&mut self.options, &mut errors);
+        validate_future("expiration_date", self.expiration_date, now, &mut errors);
+        validate_future("opens_at", self.opens_at, now, &mut errors);
+        if let Err(window) = validate_window(self.opens_at, self.expiration_date) {
+            errors.extend(window);
+        }
+        if let Err(rules) = validate_selection_rules(
+            self.voting_method,
+            self.min_selections,
+            self.max_selections,
+            self.options.len(),
+        ) {
+            errors.extend(rules);
+        }
+        if let Err(rules) = validate_score_rules(
+            self.voting_method,
+            self.min_score,
+            self.max_score,
+            self.star_runoff,
+        ) {
+            errors.extend(rules);
+        }
+        if let Err(roll) = validate_voter_roll(self.voting_method, self.voter_roll.as_ref()) {
+            errors.extend(roll);
+        }
+
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Builds the poll to store, with zeroed counts and no voters. It starts as a draft when
+    /// asked, scheduled when given an opening time, and active otherwise.
+    pub fn into_poll(self, poll_id: i64, creator: String, now: DateTime<Utc>) -> Poll {
+        let status = if self.draft {
+            PollStatus::Draft
+        } else if self.opens_at.is_some() {
+            PollStatus::Scheduled
+        } else {
+            PollStatus::Active
+        };
+        Poll {
+            poll_id,
+            title: self.title,
+            creator,
+            description: self.description,
+            created_at: now,
+            expiration_date: self.expiration_date,
+            status,
+            options: into_options(self.options, self.voter_roll.is_some()),
+            users_voted: Vec::new(),
+            allow_vote_changes: self.allow_vote_changes,
+            opens_at: self.opens_at,
+            voting_method: self.voting_method,
+            min_selections: self.min_selections.unwrap_or_else(default_min_selections),
+            max_selections: self.max_selections,
+            min_score: self.min_score.unwrap_or_default(),
+            max_score: self.max_score.unwrap_or_else(default_max_score),
+            star_runoff: self.star_runoff,
+            voter_roll: self.voter_roll,
+        }
+    }
+}
+
+impl UpdatePollRequest {
+    /// Trims the text fields and checks the ones present, returning all the problems found.
+    pub fn validate(mut self, now: DateTime<Utc>) -> Result<Self, Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        if let Some(title) = &mut self.title {
+            validate_title(title, &mut errors);
+        }
+        if let Some(description) = &mut self.description {
+            *description = description.trim().to_string();
+        }
+        if let Some(options) = &mut self.options {
+            validate_options(options, &mut errors);
+        }
+        validate_future("expiration_date", self.expiration_date, now, &mut errors);
+        validate_future("opens_at", self.opens_at, now, &mut errors);
+
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The statuses a poll may be in for every change in the request to be allowed: the text
+    /// and options only in a draft, the opening time until the poll opens, and the deadline
+    /// until it ends.
+    pub fn editable_in(&self) -> &'static [PollStatus] {
+        if self.title.is_some()
+            || self.description.is_some()
+            || self.options.is_some()
+            || self.voting_method.is_some()
+            || self.min_selections.is_some()
+            || self.max_selections.is_some()
+            || self.min_score.is_some()
+            || self.max_score.is_some()
+            || self.star_runoff.is_some()
+            || self.voter_roll.is_some()
+        {
+            &[PollStatus::Draft]
+        } else if self.opens_at.is_some() {
+            &[PollStatus::Draft, PollStatus::Scheduled]
+        } else if self.expiration_date.is_some() {
+            &[PollStatus::Draft, PollStatus::Scheduled, PollStatus::Active]
+        } else {
+            &PollStatus::ALL
+        }
+    }
+
+    /// The option list to store in place of the current one, with zeroed counts. Weights are
+    /// zeroed too when the request sets a voter roll.
+    pub fn new_options(&self) -> Option<Vec<PollOption>> {
+        let weighted = self.voter_roll.is_some();
+        self.options
+            .clone()
+            .map(|texts| into_options(texts, weighted))
+    }
+}
+
+/// Result of casting, changing or withdrawing a vote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoteOutcome {
+    /// The vote was counted and added to the voter's history
+    Recorded,
+    /// The voter already voted in this poll; nothing was written
+    AlreadyVoted,
+    /// The poll does not exist
+    PollNotFound,
+    /// The poll is not accepting votes
+    PollNotActive,
+    /// The poll's deadline has passed
+    PollExpired,
+    /// The poll is scheduled and has not opened yet
+    NotYetOpen,
+    /// The ballot does not suit the poll, for the reason given
+    InvalidBallot(String),
+    /// There is no vote by this user to change or withdraw
+    NotVoted,
+    /// The poll's owner has turned off changing votes
+    ChangesDisabled,
+    /// The poll is weighted and the voter is not on its roll
+    NotOnRoll,
+}
+
+/// A poll's standing: the poll with its per-option counts, plus how many ballots were cast
+/// and, for polls that keep their ballots, the tally worked out from them.
+#[derive(Debug, Serialize, Clone)]
+pub struct PollResults {
+    #[serde(flatten)]
+    pub poll: Poll,
+    pub ballots: usize,
+    /// Every elimination round of a ranked-choice poll
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runoff: Option<Runoff>,
+    /// The pairwise count and strongest paths of a Schulze poll
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schulze: Option<Schulze>,
+    /// Each option's ratings in a score poll
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scores: Option<ScoreTally>,
+    /// The summed weight of every ballot in a weighted poll; `ballots` is the headcount
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_weight: Option<u64>,
+}
+
+impl PollResults {
+    /// Tallies `ballots`, which must be this poll's stored ballots if it keeps any.
+    pub fn new(poll: Poll, ballots: &[Ballot]) -> Self {
+        let option_ids: Vec<i64> = poll.options.iter().map(|option| option.option_id).collect();
+        let rankings: Vec<Vec<i64>> = ballots
+            .iter()
+            .map(|ballot| ballot.ranking.clone())
+            .collect();
+        let mut results = PollResults {
+            ballots: poll.users_voted.len(),
+            runoff: None,
+            schulze: None,
+            scores: None,
+            total_weight: poll.voter_roll.as_ref().map(|roll| {
+                poll.users_voted
+                    .iter()
+                    .filter_map(|voter| roll.get(voter))
+                    .map(|&weight| u64::from(weight))
+                    .sum()
+            }),
+            poll,
+        };
+        match results.poll.voting_method {
+            VotingMethod::RankedChoice => {
+                results.runoff = Some(instant_runoff(&option_ids, &rankings));
+            }
+            VotingMethod::Schulze => results.schulze = Some(schulze(&option_ids, &rankings)),
+            VotingMethod::Score => {
+                let scores: Vec<Vec<OptionScore>> =
+                    ballots.iter().map(|ballot| ballot.scores.clone()).collect();
+                results.scores = Some(score_tally(
+                    &option_ids,
+                    results.poll.min_score,
+                    results.poll.max_score,
+                    &scores,
+                    results.poll.star_runoff,
+                ));
+            }
+            VotingMethod::SingleChoice | VotingMethod::Approval => {}
+        }
+        results
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -48,3 +892,55 @@ impl Stream for ServerEvents {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{self, doc, Bson};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Dated {
+        #[serde(default, with = "stored_date")]
+        at: Option<DateTime<Utc>>,
+    }
+
+    fn noon() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-18T12:00:00.250Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn stored_dates_are_bson_dates_in_mongodb() {
+        let dated = Dated { at: Some(noon()) };
+        let raw = bson::to_raw_document_buf(&dated).unwrap();
+
+        let stored = raw.to_document().unwrap();
+        assert!(matches!(stored.get("at"), Some(Bson::DateTime(_))));
+        assert_eq!(bson::from_slice::<Dated>(raw.as_bytes()).unwrap(), dated);
+    }
+
+    #[test]
+    fn stored_dates_are_rfc3339_strings_in_json() {
+        let dated = Dated { at: Some(noon()) };
+        let json = serde_json::to_value(&dated).unwrap();
+
+        assert_eq!(json["at"], "2026-10-18T12:00:00.250Z");
+        assert_eq!(serde_json::from_value::<Dated>(json).unwrap(), dated);
+    }
+
+    #[test]
+    fn stored_dates_read_strings_nulls_and_missing_fields() {
+        let read = |document: bson::Document| {
+            let raw = bson::to_vec(&document).unwrap();
+            bson::from_slice::<Dated>(&raw).unwrap().at
+        };
+
+        assert_eq!(
+            read(doc! { "at": "2026-10-18T12:00:00.250Z" }),
+            Some(noon())
+        );
+        assert_eq!(read(doc! { "at": Bson::Null }), None);
+        assert_eq!(read(doc! {}), None);
+    }
+}
@@ -0,0 +1,49 @@
+use rand::Rng;
+use serde::Deserialize;
+
+/// Number of codes in a freshly generated set.
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Characters used in codes; look-alikes such as 0/O and 1/I are left out.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// A set of one-time recovery codes. `codes` is shown to the user exactly once; only `hashes`
+/// is stored.
+pub struct RecoveryCodes {
+    pub codes: Vec<String>,
+    pub hashes: Vec<String>,
+}
+
+impl RecoveryCodes {
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let chars: String = (0..10)
+                    .map(|_| {
+                        RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())]
+                            as char
+                    })
+                    .collect();
+                format!("{}-{}", &chars[..5], &chars[5..])
+            })
+            .collect();
+        let hashes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+        RecoveryCodes { codes, hashes }
+    }
+}
+
+/// SHA-256 of a code, ignoring case, dashes and whitespace so that codes can be typed loosely.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    sha256::digest(normalized)
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RecoveryRequest {
+    pub user_name: String,
+    pub code: String,
+}
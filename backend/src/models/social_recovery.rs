@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A user's opt-in social recovery setup. A random recovery secret was split so that any
+/// `threshold` of the trustees' shares rebuild it; only its SHA-256 is kept here.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoverySetup {
+    pub owner_id: String,
+    pub threshold: u32,
+    pub trustee_ids: Vec<String>,
+    pub secret_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One trustee's share of an owner's recovery secret, as produced by `secret_sharing_algos`.
+/// The server keeps it only until the trustee collects it, and sees it again only when the
+/// trustee submits it to approve a recovery.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrusteeShare {
+    pub owner_id: String,
+    pub trustee_id: String,
+    pub share: String,
+}
+
+/// A recovery started by an owner who lost their passkeys. Trustees approve it by submitting
+/// their shares into `approvals`; the owner completes it by proving they started it with
+/// the secret whose hash is `request_secret_hash`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SocialRecoveryRequest {
+    pub request_id: String,
+    pub owner_id: String,
+    pub owner_name: String,
+    pub threshold: u32,
+    pub request_secret_hash: String,
+    pub approvals: Vec<TrusteeShare>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NominateTrusteesRequest {
+    /// Usernames of the trustees
+    pub trustees: Vec<String>,
+    pub threshold: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApproveSocialRecoveryRequest {
+    /// The share the trustee collected when they were nominated
+    pub share: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StartSocialRecoveryRequest {
+    pub user_name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompleteSocialRecoveryRequest {
+    pub request_secret: String,
+}
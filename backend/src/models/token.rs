@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A refresh token as issued to a client. Only the SHA-256 of the token itself is stored.
+/// Every token minted by rotating another one shares its `family_id`, so presenting an
+/// already-rotated token can revoke the whole chain.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub user_id: String,
+    pub family_id: String,
+    /// `jti` of the access token issued together with this refresh token
+    pub access_jti: String,
+    pub access_expires_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Result of presenting a refresh token.
+#[derive(Debug, Clone)]
+pub enum RefreshOutcome {
+    /// The token was live and has now been marked as used
+    Valid(RefreshToken),
+    /// The token had already been used or revoked
+    Reused(RefreshToken),
+    /// The token is unknown or expired
+    Invalid,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
@@ -1,11 +1,69 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use webauthn_rs::prelude::*;
 
+/// One entry in a user's voting history.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Votes {
     pub poll_id: i64,
+    /// The first option selected; all there was before ballots could select several
     pub option_id: i64,
+    /// Every option selected, empty in history written before ballots could select several
+    #[serde(default)]
+    pub option_ids: Vec<i64>,
+}
+
+impl Votes {
+    /// A history entry for a ballot selecting `option_ids`, which must not be empty.
+    pub fn new(poll_id: i64, option_ids: Vec<i64>) -> Self {
+        Votes {
+            poll_id,
+            option_id: option_ids[0],
+            option_ids,
+        }
+    }
+
+    /// The options this ballot selected.
+    pub fn selection(&self) -> Vec<i64> {
+        if self.option_ids.is_empty() {
+            vec![self.option_id]
+        } else {
+            self.option_ids.clone()
+        }
+    }
+}
+
+/// Roles granted to a user; every account is a member, admins may also manage any poll.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    Member,
+}
+
+fn default_roles() -> Vec<Role> {
+    vec![Role::Member]
+}
+
+/// User-facing metadata for one of the passkeys in `User.keys`, matched by credential ID.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasskeyDetails {
+    pub cred_id: String,
+    pub nickname: String,
+    pub created_at: Option<DateTime<Utc>>, // Unknown for keys enrolled before details were tracked
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl PasskeyDetails {
+    pub fn new(key: &Passkey, nickname: String) -> Self {
+        PasskeyDetails {
+            cred_id: key.cred_id().to_string(),
+            nickname,
+            created_at: Some(Utc::now()),
+            last_used: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -15,4 +73,33 @@ pub struct User {
     pub polls_voted: Option<Vec<Votes>>,
     pub owned_polls: Option<Vec<i64>>,
     pub keys: Vec<Passkey>,
+    #[serde(default)]
+    pub passkey_details: Vec<PasskeyDetails>,
+    #[serde(default = "default_roles")]
+    pub roles: Vec<Role>,
+    /// SHA-256 hashes of the unused recovery codes
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+}
+
+impl User {
+    /// Details for every key on the account, filling in defaults for keys that have none.
+    pub fn list_passkeys(&self) -> Vec<PasskeyDetails> {
+        self.keys
+            .iter()
+            .map(|key| {
+                let cred_id = key.cred_id().to_string();
+                self.passkey_details
+                    .iter()
+                    .find(|details| details.cred_id == cred_id)
+                    .cloned()
+                    .unwrap_or(PasskeyDetails {
+                        cred_id,
+                        nickname: "Passkey".to_string(),
+                        created_at: None,
+                        last_used: None,
+                    })
+            })
+            .collect()
+    }
 }
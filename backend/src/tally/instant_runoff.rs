@@ -0,0 +1,204 @@
+use serde::Serialize;
+
+/// One option's share of a round.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct OptionVotes {
+    pub option_id: i64,
+    pub votes: usize,
+}
+
+/// One round of counting: every ballot goes to its highest-ranked option still in the running.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Round {
+    /// Options still in the running, in the poll's order
+    pub tallies: Vec<OptionVotes>,
+    /// Ballots that rank none of the options still in the running
+    pub exhausted: usize,
+    /// The option knocked out at the end of this round; none in the final round
+    pub eliminated: Option<i64>,
+    /// Whether several options shared the fewest votes and the tie-break picked `eliminated`
+    pub tie_broken: bool,
+}
+
+/// Every round of an instant-runoff count and who came out on top.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Runoff {
+    pub rounds: Vec<Round>,
+    /// The option holding a majority of the ballots still in play; none without any ballots
+    pub winner: Option<i64>,
+}
+
+/// Counts ranked `ballots` over `option_ids` by instant runoff, eliminating one option a round
+/// until one holds a majority of the ballots not yet exhausted.
+///
+/// Ties for fewest votes are broken deterministically: the tied option with fewer votes in the
+/// latest earlier round where they differ goes out, and failing that the one listed last in the
+/// poll. Options a ballot ranks that are not in `option_ids` are skipped.
+pub fn instant_runoff(option_ids: &[i64], ballots: &[Vec<i64>]) -> Runoff {
+    let mut continuing = option_ids.to_vec();
+    let mut rounds: Vec<Round> = Vec::new();
+
+    loop {
+        let mut votes = vec![0; continuing.len()];
+        let mut exhausted = 0;
+        for ballot in ballots {
+            match ballot
+                .iter()
+                .find_map(|choice| continuing.iter().position(|option| option == choice))
+            {
+                Some(i) => votes[i] += 1,
+                None => exhausted += 1,
+            }
+        }
+        let tallies: Vec<OptionVotes> = continuing
+            .iter()
+            .zip(&votes)
+            .map(|(&option_id, &votes)| OptionVotes { option_id, votes })
+            .collect();
+
+        let in_play = ballots.len() - exhausted;
+        let majority = votes.iter().position(|&count| count * 2 > in_play);
+        if in_play == 0 || majority.is_some() {
+            rounds.push(Round {
+                tallies,
+                exhausted,
+                eliminated: None,
+                tie_broken: false,
+            });
+            return Runoff {
+                rounds,
+                winner: majority.map(|i| continuing[i]),
+            };
+        }
+
+        let fewest = votes.iter().copied().min().unwrap_or(0);
+        let tied: Vec<i64> = tallies
+            .iter()
+            .filter(|tally| tally.votes == fewest)
+            .map(|tally| tally.option_id)
+            .collect();
+        let eliminated = break_tie(&tied, &rounds);
+        rounds.push(Round {
+            tallies,
+            exhausted,
+            eliminated: Some(eliminated),
+            tie_broken: tied.len() > 1,
+        });
+        continuing.retain(|&option| option != eliminated);
+    }
+}
+
+/// Picks which of the `tied` options, given in the poll's order, to eliminate.
+fn break_tie(tied: &[i64], earlier: &[Round]) -> i64 {
+    let mut candidates = tied.to_vec();
+    for round in earlier.iter().rev() {
+        if candidates.len() == 1 {
+            break;
+        }
+        let votes_in = |option: i64| {
+            round
+                .tallies
+                .iter()
+                .find(|tally| tally.option_id == option)
+                .map_or(0, |tally| tally.votes)
+        };
+        let fewest = candidates.iter().map(|&option| votes_in(option)).min();
+        candidates.retain(|&option| Some(votes_in(option)) == fewest);
+    }
+    candidates[candidates.len() - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ballots(groups: &[(usize, &[i64])]) -> Vec<Vec<i64>> {
+        groups
+            .iter()
+            .flat_map(|&(count, ranking)| std::iter::repeat_n(ranking.to_vec(), count))
+            .collect()
+    }
+
+    fn votes(round: &Round) -> Vec<(i64, usize)> {
+        round
+            .tallies
+            .iter()
+            .map(|tally| (tally.option_id, tally.votes))
+            .collect()
+    }
+
+    #[test]
+    fn majority_in_first_round_wins_outright() {
+        let runoff = instant_runoff(&[1, 2, 3], &ballots(&[(3, &[1, 2]), (1, &[2]), (1, &[3])]));
+
+        assert_eq!(runoff.winner, Some(1));
+        assert_eq!(runoff.rounds.len(), 1);
+        assert_eq!(votes(&runoff.rounds[0]), vec![(1, 3), (2, 1), (3, 1)]);
+        assert_eq!(runoff.rounds[0].eliminated, None);
+    }
+
+    #[test]
+    fn eliminates_one_option_a_round_until_a_majority() {
+        let runoff = instant_runoff(
+            &[1, 2, 3, 4],
+            &ballots(&[(4, &[1, 4]), (3, &[2, 3]), (2, &[3, 2]), (1, &[4, 3, 2])]),
+        );
+
+        assert_eq!(runoff.rounds.len(), 3);
+        assert_eq!(runoff.rounds[0].eliminated, Some(4));
+        assert_eq!(votes(&runoff.rounds[1]), vec![(1, 4), (2, 3), (3, 3)]);
+        // 2 and 3 tie on 3; 3 had fewer in round 1
+        assert_eq!(runoff.rounds[1].eliminated, Some(3));
+        assert!(runoff.rounds[1].tie_broken);
+        assert_eq!(votes(&runoff.rounds[2]), vec![(1, 4), (2, 6)]);
+        assert_eq!(runoff.winner, Some(2));
+    }
+
+    #[test]
+    fn exhausted_ballots_leave_the_majority_threshold() {
+        let runoff = instant_runoff(&[1, 2, 3], &ballots(&[(2, &[1]), (2, &[2]), (1, &[3])]));
+
+        assert_eq!(runoff.rounds[0].eliminated, Some(3));
+        let last = &runoff.rounds[1];
+        assert_eq!(last.exhausted, 1);
+        // 2 and 2 of the 4 ballots still in play: no majority, so the tie-break decides
+        assert_eq!(last.eliminated, Some(2));
+        assert!(last.tie_broken);
+        assert_eq!(runoff.winner, Some(1));
+        assert_eq!(runoff.rounds[2].exhausted, 3);
+    }
+
+    #[test]
+    fn tie_goes_against_fewer_votes_in_an_earlier_round() {
+        let runoff = instant_runoff(
+            &[1, 2, 3, 4],
+            &ballots(&[(4, &[1]), (2, &[2]), (3, &[3]), (1, &[4, 2])]),
+        );
+
+        // 2 and 3 tie on 3 in round 2; 2 had fewer in round 1, though 3 is listed later
+        assert_eq!(votes(&runoff.rounds[1]), vec![(1, 4), (2, 3), (3, 3)]);
+        assert_eq!(runoff.rounds[1].eliminated, Some(2));
+        assert!(runoff.rounds[1].tie_broken);
+        assert_eq!(runoff.winner, Some(1));
+    }
+
+    #[test]
+    fn tie_without_an_earlier_difference_goes_against_the_last_listed() {
+        let runoff = instant_runoff(&[1, 2, 3], &ballots(&[(2, &[1]), (1, &[2]), (1, &[3])]));
+
+        assert_eq!(runoff.rounds[0].eliminated, Some(3));
+        assert!(runoff.rounds[0].tie_broken);
+        // With 3 gone its ballot exhausts, and 1 holds 2 of the 3 still in play
+        assert_eq!(runoff.winner, Some(1));
+    }
+
+    #[test]
+    fn no_ballots_means_no_winner() {
+        let runoff = instant_runoff(&[1, 2], &[]);
+
+        assert_eq!(runoff.winner, None);
+        assert_eq!(runoff.rounds.len(), 1);
+        assert_eq!(votes(&runoff.rounds[0]), vec![(1, 0), (2, 0)]);
+        assert_eq!(runoff.rounds[0].eliminated, None);
+    }
+}
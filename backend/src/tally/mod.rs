@@ -0,0 +1,6 @@
+//! Counting methods that work from individually stored ballots rather than per-option counters.
+//! Everything here is pure: it takes the poll's options and ballots and returns the outcome.
+
+pub mod instant_runoff;
+pub mod schulze;
+pub mod score;
@@ -0,0 +1,200 @@
+use serde::Serialize;
+
+/// A pairwise count of ranked ballots and the Schulze ranking drawn from it. Row and column `i`
+/// of both tables refer to `options[i]`.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Schulze {
+    /// Option IDs, in the poll's order
+    pub options: Vec<i64>,
+    /// `pairwise[i][j]` is how many ballots prefer option `i` to option `j`
+    pub pairwise: Vec<Vec<usize>>,
+    /// `strongest_paths[i][j]` is the strength of the strongest beatpath from `i` to `j`
+    pub strongest_paths: Vec<Vec<usize>>,
+    /// The option that beats every other head to head, if there is one
+    pub condorcet_winner: Option<i64>,
+    /// Options best first; options with as many Schulze wins share a place
+    pub ranking: Vec<Vec<i64>>,
+}
+
+/// Counts ranked `ballots` over `option_ids` pairwise and ranks them by the Schulze method.
+///
+/// A ballot prefers every option it ranks to every option it leaves out, and is indifferent
+/// between the ones it leaves out. Options a ballot ranks that are not in `option_ids` are
+/// skipped.
+pub fn schulze(option_ids: &[i64], ballots: &[Vec<i64>]) -> Schulze {
+    let n = option_ids.len();
+    let pairwise = pairwise_preferences(option_ids, ballots);
+
+    let condorcet_winner = (0..n)
+        .find(|&i| (0..n).all(|j| i == j || pairwise[i][j] > pairwise[j][i]))
+        .map(|i| option_ids[i]);
+
+    // Floyd–Warshall over the defeats, widest path rather than shortest
+    let mut paths = vec![vec![0; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            if i != j && pairwise[i][j] > pairwise[j][i] {
+                paths[i][j] = pairwise[i][j];
+            }
+        }
+    }
+    for k in 0..n {
+        for i in 0..n {
+            if i == k {
+                continue;
+            }
+            for j in 0..n {
+                if j != i && j != k {
+                    paths[i][j] = paths[i][j].max(paths[i][k].min(paths[k][j]));
+                }
+            }
+        }
+    }
+
+    let wins: Vec<usize> = (0..n)
+        .map(|i| (0..n).filter(|&j| paths[i][j] > paths[j][i]).count())
+        .collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    // Stable, so options with as many wins keep the poll's order within their place
+    order.sort_by_key(|&i| std::cmp::Reverse(wins[i]));
+    let mut ranking: Vec<Vec<i64>> = Vec::new();
+    let mut last_wins = None;
+    for i in order {
+        if last_wins == Some(wins[i]) {
+            if let Some(place) = ranking.last_mut() {
+                place.push(option_ids[i]);
+            }
+        } else {
+            ranking.push(vec![option_ids[i]]);
+            last_wins = Some(wins[i]);
+        }
+    }
+
+    Schulze {
+        options: option_ids.to_vec(),
+        pairwise,
+        strongest_paths: paths,
+        condorcet_winner,
+        ranking,
+    }
+}
+
+/// `d[i][j]`: how many ballots rank option `i` above option `j`.
+fn pairwise_preferences(option_ids: &[i64], ballots: &[Vec<i64>]) -> Vec<Vec<usize>> {
+    let n = option_ids.len();
+    let mut preferences = vec![vec![0; n]; n];
+    for ballot in ballots {
+        // Position of each option on this ballot; unranked options come after all ranked ones
+        let positions: Vec<usize> = option_ids
+            .iter()
+            .map(|option| {
+                ballot
+                    .iter()
+                    .position(|choice| choice == option)
+                    .unwrap_or(usize::MAX)
+            })
+            .collect();
+        for i in 0..n {
+            for j in 0..n {
+                if positions[i] < positions[j] {
+                    preferences[i][j] += 1;
+                }
+            }
+        }
+    }
+    preferences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ballots(groups: &[(usize, &[i64])]) -> Vec<Vec<i64>> {
+        groups
+            .iter()
+            .flat_map(|&(count, ranking)| std::iter::repeat_n(ranking.to_vec(), count))
+            .collect()
+    }
+
+    #[test]
+    fn counts_each_pair_of_options() {
+        let tally = schulze(&[1, 2, 3], &ballots(&[(2, &[1, 2, 3]), (1, &[3, 1])]));
+
+        assert_eq!(tally.options, vec![1, 2, 3]);
+        assert_eq!(
+            tally.pairwise,
+            vec![vec![0, 3, 2], vec![0, 0, 2], vec![1, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn finds_the_condorcet_winner() {
+        let tally = schulze(&[1, 2, 3], &ballots(&[(2, &[1, 2, 3]), (1, &[3, 1])]));
+
+        assert_eq!(tally.condorcet_winner, Some(1));
+        assert_eq!(tally.ranking, vec![vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn resolves_a_cycle_by_strongest_paths() {
+        // The 45-voter example from the Schulze method article, with A to E as 1 to 5
+        let tally = schulze(
+            &[1, 2, 3, 4, 5],
+            &ballots(&[
+                (5, &[1, 3, 2, 5, 4]),
+                (5, &[1, 4, 5, 3, 2]),
+                (8, &[2, 5, 4, 1, 3]),
+                (3, &[3, 1, 2, 5, 4]),
+                (7, &[3, 1, 5, 2, 4]),
+                (2, &[3, 2, 1, 4, 5]),
+                (7, &[4, 3, 5, 2, 1]),
+                (8, &[5, 2, 1, 4, 3]),
+            ]),
+        );
+
+        assert_eq!(tally.condorcet_winner, None);
+        assert_eq!(
+            tally.pairwise,
+            vec![
+                vec![0, 20, 26, 30, 22],
+                vec![25, 0, 16, 33, 18],
+                vec![19, 29, 0, 17, 24],
+                vec![15, 12, 28, 0, 14],
+                vec![23, 27, 21, 31, 0],
+            ]
+        );
+        assert_eq!(
+            tally.strongest_paths,
+            vec![
+                vec![0, 28, 28, 30, 24],
+                vec![25, 0, 28, 33, 24],
+                vec![25, 29, 0, 29, 24],
+                vec![25, 28, 28, 0, 24],
+                vec![25, 28, 28, 31, 0],
+            ]
+        );
+        assert_eq!(
+            tally.ranking,
+            vec![vec![5], vec![1], vec![3], vec![2], vec![4]]
+        );
+    }
+
+    #[test]
+    fn options_with_as_many_wins_share_a_place() {
+        let tally = schulze(&[1, 2, 3], &ballots(&[(1, &[1, 2, 3]), (1, &[2, 1, 3])]));
+
+        assert_eq!(tally.condorcet_winner, None);
+        assert_eq!(tally.ranking, vec![vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn unranked_options_trail_ranked_ones_and_tie_among_themselves() {
+        // Option 9 is not in the poll and is skipped
+        let tally = schulze(&[1, 2, 3, 4], &ballots(&[(1, &[9, 2])]));
+
+        assert_eq!(tally.pairwise[1], vec![1, 0, 1, 1]);
+        assert_eq!(tally.pairwise[0], vec![0, 0, 0, 0]);
+        assert_eq!(tally.condorcet_winner, Some(2));
+        assert_eq!(tally.ranking, vec![vec![2], vec![1, 3, 4]]);
+    }
+}
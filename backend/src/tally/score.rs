@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+
+/// The score one ballot gives one option.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct OptionScore {
+    pub option_id: i64,
+    pub score: u32,
+}
+
+/// How one option was rated across every ballot.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct OptionRating {
+    pub option_id: i64,
+    pub total: u64,
+    /// None until someone has rated the option
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    /// `histogram[k]` is how many ballots gave the option the lowest score plus `k`
+    pub histogram: Vec<usize>,
+}
+
+/// The automatic runoff of STAR voting between the two highest-scoring options.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct StarRunoff {
+    /// The two options with the highest totals, higher first
+    pub finalists: Vec<i64>,
+    /// `preferred[i]` is how many ballots scored `finalists[i]` above the other finalist
+    pub preferred: Vec<usize>,
+    /// Ballots that scored both finalists the same
+    pub no_preference: usize,
+    /// None without any ballots or without two options to choose between
+    pub winner: Option<i64>,
+}
+
+/// A score poll's ratings, and its STAR runoff when the poll asks for one.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ScoreTally {
+    pub options: Vec<OptionRating>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub star: Option<StarRunoff>,
+}
+
+/// Summarises score `ballots` over `option_ids` on a scale of `min_score` to `max_score`,
+/// running the STAR runoff if `star_runoff` is set. Scores outside the scale and options not in
+/// `option_ids` are skipped.
+///
+/// Options with equal totals are ordered as the poll lists them when picking finalists. A
+/// runoff with as many ballots each way goes to the finalist with the higher total, and failing
+/// that to the first finalist.
+pub fn score_tally(
+    option_ids: &[i64],
+    min_score: u32,
+    max_score: u32,
+    ballots: &[Vec<OptionScore>],
+    star_runoff: bool,
+) -> ScoreTally {
+    let scale = (min_score..=max_score).count();
+    let options: Vec<OptionRating> = option_ids
+        .iter()
+        .map(|&option_id| {
+            let mut scores: Vec<u32> = ballots
+                .iter()
+                .filter_map(|ballot| score_for(ballot, option_id))
+                .filter(|score| (min_score..=max_score).contains(score))
+                .collect();
+            scores.sort_unstable();
+            let mut histogram = vec![0; scale];
+            for score in &scores {
+                histogram[(score - min_score) as usize] += 1;
+            }
+            let total: u64 = scores.iter().map(|&score| u64::from(score)).sum();
+            OptionRating {
+                option_id,
+                total,
+                mean: (!scores.is_empty()).then(|| total as f64 / scores.len() as f64),
+                median: median(&scores),
+                histogram,
+            }
+        })
+        .collect();
+
+    let star = star_runoff.then(|| star(&options, ballots));
+    ScoreTally { options, star }
+}
+
+fn score_for(ballot: &[OptionScore], option_id: i64) -> Option<u32> {
+    ballot
+        .iter()
+        .find(|rating| rating.option_id == option_id)
+        .map(|rating| rating.score)
+}
+
+/// Median of `sorted`, halfway between the middle two when there is an even number.
+fn median(sorted: &[u32]) -> Option<f64> {
+    let middle = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(f64::from(sorted[middle])),
+        _ => Some((f64::from(sorted[middle - 1]) + f64::from(sorted[middle])) / 2.0),
+    }
+}
+
+fn star(options: &[OptionRating], ballots: &[Vec<OptionScore>]) -> StarRunoff {
+    let mut by_total: Vec<&OptionRating> = options.iter().collect();
+    // Stable, so equal totals keep the poll's order
+    by_total.sort_by_key(|rating| std::cmp::Reverse(rating.total));
+    let finalists: Vec<i64> = by_total
+        .iter()
+        .take(2)
+        .map(|rating| rating.option_id)
+        .collect();
+    let [first, second] = finalists[..] else {
+        return StarRunoff {
+            winner: None,
+            preferred: vec![0; finalists.len()],
+            finalists,
+            no_preference: 0,
+        };
+    };
+
+    let (mut for_first, mut for_second, mut no_preference) = (0, 0, 0);
+    for ballot in ballots {
+        let first_score = score_for(ballot, first).unwrap_or(0);
+        let second_score = score_for(ballot, second).unwrap_or(0);
+        match first_score.cmp(&second_score) {
+            std::cmp::Ordering::Greater => for_first += 1,
+            std::cmp::Ordering::Less => for_second += 1,
+            std::cmp::Ordering::Equal => no_preference += 1,
+        }
+    }
+    // `first` has at least the higher total, so it also takes a tied runoff
+    let winner = if ballots.is_empty() {
+        None
+    } else if for_second > for_first {
+        Some(second)
+    } else {
+        Some(first)
+    };
+
+    StarRunoff {
+        finalists,
+        preferred: vec![for_first, for_second],
+        no_preference,
+        winner,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ballot(scores: &[(i64, u32)]) -> Vec<OptionScore> {
+        scores
+            .iter()
+            .map(|&(option_id, score)| OptionScore { option_id, score })
+            .collect()
+    }
+
+    #[test]
+    fn averages_each_options_scores() {
+        let tally = score_tally(
+            &[1, 2],
+            0,
+            5,
+            &[
+                ballot(&[(1, 1), (2, 5)]),
+                ballot(&[(1, 2), (2, 5)]),
+                ballot(&[(1, 4), (2, 5)]),
+            ],
+            false,
+        );
+
+        assert_eq!(tally.options[0].total, 7);
+        assert_eq!(tally.options[0].mean, Some(7.0 / 3.0));
+        assert_eq!(tally.options[0].median, Some(2.0));
+        assert_eq!(tally.options[1].mean, Some(5.0));
+        assert_eq!(tally.star, None);
+    }
+
+    #[test]
+    fn median_of_an_even_count_is_halfway_between_the_middle_two() {
+        let ballots: Vec<Vec<OptionScore>> = [5, 1, 4, 2]
+            .iter()
+            .map(|&score| ballot(&[(1, score)]))
+            .collect();
+        let tally = score_tally(&[1], 0, 5, &ballots, false);
+
+        assert_eq!(tally.options[0].median, Some(3.0));
+    }
+
+    #[test]
+    fn histogram_starts_at_the_lowest_score() {
+        let tally = score_tally(
+            &[1],
+            1,
+            3,
+            &[ballot(&[(1, 1)]), ballot(&[(1, 3)]), ballot(&[(1, 3)])],
+            false,
+        );
+
+        assert_eq!(tally.options[0].histogram, vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn no_ballots_leave_the_averages_unset() {
+        let tally = score_tally(&[1, 2], 0, 5, &[], true);
+
+        assert_eq!(tally.options[0].mean, None);
+        assert_eq!(tally.options[0].median, None);
+        assert_eq!(tally.options[0].histogram, vec![0; 6]);
+        assert_eq!(tally.star.and_then(|star| star.winner), None);
+    }
+
+    #[test]
+    fn star_finalists_with_equal_totals_follow_the_poll_order() {
+        let tally = score_tally(
+            &[1, 2, 3],
+            0,
+            5,
+            &[
+                ballot(&[(1, 1), (2, 4), (3, 4)]),
+                ballot(&[(1, 5), (2, 2), (3, 2)]),
+            ],
+            true,
+        );
+
+        // 1, 2 and 3 all total 6
+        let star = tally.star.expect("STAR runoff requested");
+        assert_eq!(star.finalists, vec![1, 2]);
+        assert_eq!(star.preferred, vec![1, 1]);
+        assert_eq!(star.winner, Some(1));
+    }
+
+    #[test]
+    fn star_runoff_goes_to_the_finalist_preferred_by_more_ballots() {
+        let tally = score_tally(
+            &[1, 2],
+            0,
+            5,
+            &[
+                ballot(&[(1, 5), (2, 0)]),
+                ballot(&[(1, 3), (2, 4)]),
+                ballot(&[(1, 3), (2, 4)]),
+                ballot(&[(1, 2), (2, 2)]),
+            ],
+            true,
+        );
+
+        let star = tally.star.expect("STAR runoff requested");
+        // 1 has the higher total, but more ballots score 2 above it
+        assert_eq!(star.finalists, vec![1, 2]);
+        assert_eq!(star.preferred, vec![1, 2]);
+        assert_eq!(star.no_preference, 1);
+        assert_eq!(star.winner, Some(2));
+    }
+
+    #[test]
+    fn tied_star_runoff_goes_to_the_higher_total() {
+        let tally = score_tally(
+            &[1, 2],
+            0,
+            5,
+            &[ballot(&[(1, 1), (2, 2)]), ballot(&[(1, 5), (2, 1)])],
+            true,
+        );
+
+        let star = tally.star.expect("STAR runoff requested");
+        assert_eq!(star.finalists, vec![1, 2]);
+        assert_eq!(star.preferred, vec![1, 1]);
+        assert_eq!(star.winner, Some(1));
+    }
+}
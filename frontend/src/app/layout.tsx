@@ -22,7 +22,8 @@ export default function RootLayout({
 }: Readonly<{
   children: React.ReactNode;
 }>) {
-  const { name, ClearUser, clearOwnedPolls } = useUserStore();
+  const apiUrl = process.env.NEXT_PUBLIC_API_URL || '';
+  const { name, token, ClearUser, clearOwnedPolls } = useUserStore();
 
   return (
     <html lang="en">
@@ -50,6 +51,11 @@ export default function RootLayout({
                   <li>
                     <button
                       onClick={() => {
+                        // Revoke the session server-side; clear local state either way
+                        fetch(`${apiUrl}/api/auth/logout`, {
+                          method: "POST",
+                          headers: { Authorization: `Bearer ${token}` },
+                        }).catch(() => {});
                         clearOwnedPolls(name);
                         ClearUser(name);
                       }}
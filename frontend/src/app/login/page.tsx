@@ -31,7 +31,7 @@ export default function Register() {
       console.log("auth Response\n", authResponse);
 
       // Step 3: Send the authenticator response to the server for verification
-      const verificationResponse = await fetch(`${apiUrl}/api/auth/finish_auth/`+name, {
+      const verificationResponse = await fetch(`${apiUrl}/api/auth/finish_auth/` + name + `?ceremony_id=` + respJSON.ceremony_id, {
         method: 'POST',
         headers: {
           'Content-Type': 'application/json',
@@ -44,7 +44,40 @@ export default function Register() {
 
       if (verificationResult.token) {
         setSuccessMessage("Authentication successful!");
-        userStore.setUser(name);
+        userStore.setUser(name, verificationResult.user_id, verificationResult.token, verificationResult.refresh_token);
+        userStore.setOwnedPolls(polls)
+      } else {
+        setErrorMessage(`Authentication failed. Details: ${JSON.stringify(verificationResult)}`);
+      }
+    } catch (error) {
+      if (error instanceof Error) {
+        setErrorMessage(`Error: ${error.message}`);
+      }
+    }
+  };
+
+  const handleDiscoverableAuthentication = async () => {
+    setSuccessMessage('');
+    setErrorMessage('');
+
+    try {
+      // The browser picks the account, so no username is sent
+      const response = await fetch(`${apiUrl}/api/auth/start_discoverable_auth`, { method: 'POST' });
+      const respJSON = await response.json();
+      const authResponse = await startAuthentication({ optionsJSON: respJSON.publicKey });
+
+      const verificationResponse = await fetch(`${apiUrl}/api/auth/finish_discoverable_auth?ceremony_id=` + respJSON.ceremony_id, {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(authResponse),
+      });
+      const verificationResult = await verificationResponse.json();
+
+      if (verificationResult.token) {
+        setSuccessMessage("Authentication successful!");
+        userStore.setUser(verificationResult.user_name, verificationResult.user_id, verificationResult.token, verificationResult.refresh_token);
         userStore.setOwnedPolls(polls)
       } else {
         setErrorMessage(`Authentication failed. Details: ${JSON.stringify(verificationResult)}`);
@@ -69,6 +102,7 @@ export default function Register() {
               id="name"
               name="name"
               type="text"
+              autoComplete="username webauthn"
               required
               className="mt-2 block w-full p-2 border rounded-md text-black focus:outline-none focus:ring-2 focus:ring-black"
               onChange={(e) => setName(e.target.value)}
@@ -80,6 +114,13 @@ export default function Register() {
           >
             Login
           </button>
+          <button
+            type="button"
+            onClick={handleDiscoverableAuthentication}
+            className="w-full py-2 border border-black text-black rounded-md font-medium shadow hover:bg-gray-100"
+          >
+            Sign in with a passkey
+          </button>
           <a href="/register">New User? Register</a>
           {successMessage && (
             <p className="mt-4 text-center text-green-600">
@@ -1,5 +1,6 @@
 "use client";
 import usePollStore from "@/stores/usePollStore";
+import useUserStore from "@/stores/useUserStore";
 import { redirect } from "next/navigation";
 import { useState, useEffect } from "react";
 
@@ -16,12 +17,16 @@ export default function Home() {
   const [loading, setLoading] = useState(true); // Loading state
   const [error, setError] = useState<string | null>(null); // Error state
   const {polls, initPolls} = usePollStore();
+  const { token } = useUserStore();
   // Fetch data on mount
   useEffect(() => {
     const fetchData = async () => {
       try {
 
-        const data = await fetch(`${apiUrl}/api/polls/0`);
+        // Signed-in users also get their own drafts and scheduled polls
+        const data = await fetch(`${apiUrl}/api/polls/0`, {
+          headers: token ? { Authorization: `Bearer ${token}` } : {},
+        });
         const resp = await data.json();
         console.log(resp);
         initPolls(resp);
@@ -37,7 +42,7 @@ export default function Home() {
     };
 
     fetchData();
-  }, []);
+  }, [token]);
 
 
   // Handle loading and error states
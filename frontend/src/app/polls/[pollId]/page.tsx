@@ -15,9 +15,10 @@ export interface Poll {
   description: string;
   created_at: string;
   expiration_date?: string | null;
-  status: "active" | "expired" | "closed";
+  status: "draft" | "scheduled" | "active" | "closed" | "expired" | "archived";
   options: PollOption[];
   users_voted: string[];
+  allow_vote_changes?: boolean;
 
   [key: string]: string|number|null|string[]| PollOption[]| undefined;
 }
@@ -30,7 +31,7 @@ export default function PollDetails({ params }: { params: Promise<{ pollId: stri
   const [error, setError] = useState<string | null>(null);
   const [vote, selectVote] = useState<string | null>(null);
   const [isLive, setIsLive] = useState(false);
-  const { name } = useUserStore();
+  const { name, userId, token } = useUserStore();
 
   const handleRadioChange = (value: string) => {
     selectVote(value);
@@ -66,13 +67,19 @@ export default function PollDetails({ params }: { params: Promise<{ pollId: stri
     }
     try {
       const response = await fetch(
-        `${apiUrl}/api/polls/${pollId}/vote?option_id=${vote}&username=${name}`,
+        `${apiUrl}/api/polls/${pollId}/vote?option_id=${vote}`,
         {
           method: "POST",
-          body: JSON.stringify({ user: name }),
+          headers: { Authorization: `Bearer ${token}` },
         }
       );
 
+      if (response.status === 409) {
+        const result = await response.json();
+        alert(result.error);
+        fetchData();
+        return;
+      }
       if (response.status !== 200) {
         throw new Error("Failed to submit vote");
       }
@@ -172,7 +179,7 @@ export default function PollDetails({ params }: { params: Promise<{ pollId: stri
       <button
         className="mt-6 px-6 py-2 bg-blue-600 text-white rounded-lg hover:bg-blue-700 focus:ring-4 focus:ring-blue-300"
         onClick={onSubmit}
-        disabled={post.users_voted.includes(name)}
+        disabled={post.users_voted.includes(userId)}
       >
         {name === "" ? <span>LOGIN TO VOTE</span> : <span>SUBMIT VOTE</span>}
       </button>
@@ -9,7 +9,7 @@ export default function Home() {
   const [fil, setFil] = useState("active"); // Filter state
   const [loading, setLoading] = useState(true); // Loading state
   const [error, setError] = useState<string | null>(null); // Error state
-  const { setOwnedPolls, ownedPolls } = useUserStore();
+  const { setOwnedPolls, ownedPolls, token } = useUserStore();
   const { polls } = usePollStore();
 
   // Fetch data on mount
@@ -32,17 +32,21 @@ export default function Home() {
   }, [polls, setOwnedPolls]);
 
   // API call to update poll status
-  const updatePollStatus = async (pollId: number, action: "close" | "reset") => {
+  const updatePollStatus = async (
+    pollId: number,
+    action: "schedule" | "activate" | "close" | "archive" | "reset"
+  ) => {
     try {
-      const endpoint =
-        action === "close"
-          ? `${apiUrl}/api/polls/${pollId}/close`
-          : `${apiUrl}/api/polls/${pollId}/reset`;
+      const endpoint = `${apiUrl}/api/polls/${pollId}/${action}`;
       const response = await fetch(endpoint, {
         method: "POST",
+        headers: { Authorization: `Bearer ${token}` },
       });
 
-      if (!response.ok) {
+      if (response.status === 409) {
+        const result = await response.json();
+        alert(result.error);
+      } else if (!response.ok) {
         throw new Error(`Failed to ${action} the poll.`);
       } else {
         alert("success")
@@ -71,9 +75,12 @@ export default function Home() {
             onChange={(e) => setFil(e.target.value)}
             className="p-4 font-extrabold text-black bg-transparent"
           >
+            <option value="draft">DRAFT</option>
+            <option value="scheduled">SCHEDULED</option>
             <option value="active">ACTIVE</option>
             <option value="closed">CLOSED</option>
             <option value="expired">EXPIRED</option>
+            <option value="archived">ARCHIVED</option>
           </select>
         </div>
 
@@ -132,6 +139,30 @@ export default function Home() {
 
                 {/* Action Buttons */}
                 <div className="flex justify-between mt-4">
+                  {post.status === "draft" && (
+                    <button
+                      onClick={() => updatePollStatus(post.poll_id, "schedule")}
+                      className="px-4 py-2 text-white bg-green-500 rounded hover:bg-green-600"
+                    >
+                      Schedule Poll
+                    </button>
+                  )}
+                  {post.status === "scheduled" && (
+                    <button
+                      onClick={() => updatePollStatus(post.poll_id, "activate")}
+                      className="px-4 py-2 text-white bg-green-500 rounded hover:bg-green-600"
+                    >
+                      Open Poll
+                    </button>
+                  )}
+                  {(post.status === "closed" || post.status === "expired") && (
+                    <button
+                      onClick={() => updatePollStatus(post.poll_id, "archive")}
+                      className="px-4 py-2 text-white bg-gray-500 rounded hover:bg-gray-600"
+                    >
+                      Archive Poll
+                    </button>
+                  )}
                   <button
                     onClick={() => updatePollStatus(post.poll_id, "close")}
                     className="px-4 py-2 text-white bg-red-500 rounded hover:bg-red-600"
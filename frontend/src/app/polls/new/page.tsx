@@ -3,7 +3,6 @@
 import usePollStore from "@/stores/usePollStore";
 import useUserStore from "@/stores/useUserStore";
 import { useState, useRef } from "react";
-import { Poll } from "../[pollId]/page";
 
 export interface PollOption {
   option_id: number;
@@ -14,7 +13,7 @@ export default function New() {
   const apiUrl = process.env.NEXT_PUBLIC_API_URL || '';
   const [inputs, setInputs] = useState<string[]>(["", ""]); // List of input values
   const formRef = useRef<HTMLFormElement>(null);
-  const { name, setOwnedPolls } = useUserStore();
+  const { token, setOwnedPolls } = useUserStore();
   const { polls, addPoll } = usePollStore();
 
   // Function to add a new input
@@ -38,55 +37,35 @@ export default function New() {
     const formData = new FormData(formRef.current);
 
 
-    const formDataObject: Poll = {
-      poll_id: 0, // Default value
-      title: "",
-      description: "",
-      expiration_date: "",
-      created_at: "",
-      creator: "",
-      options: [],
-      users_voted: [],
-      status: "active", // Default value, can be changed based on the select
-    };
-    formData.forEach((value, key) => {
-      if (formDataObject.hasOwnProperty(key)) {
-        formDataObject[key as keyof Poll] = value.toString(); // Ensure correct type
-      }
-    });
-
-    const now = new Date();
-    const formattedtime = now.toISOString().slice(0, 16);
-
-    formDataObject["options"] = [];
-    formDataObject["users_voted"] = [];
-    formDataObject["poll_id"] = Number(formDataObject["poll_id"]);
-    formDataObject["expiration_date"] = formDataObject["expiration_date"] + ":00Z";
-    formDataObject["created_at"] = formattedtime + ":00Z";
-    formDataObject["creator"] = name;
-
-    inputs.forEach((value, key) => {
-      const data = {
-        option_id: key,
-        text: value,
-        votes: 0,
-      };
-      formDataObject["options"].push(data);
+    // The server assigns the ID, creator and counts; only send what the user chose
+    const expiration = formData.get("expiration_date")?.toString();
+    const data = JSON.stringify({
+      title: formData.get("title")?.toString() ?? "",
+      description: formData.get("description")?.toString() ?? "",
+      expiration_date: expiration ? new Date(expiration).toISOString() : null,
+      options: inputs,
+      allow_vote_changes: formData.get("allow_vote_changes") === "on",
+      draft: formData.get("draft") === "on",
+      voting_method: formData.get("voting_method")?.toString() ?? "single_choice",
     });
 
-    const data = JSON.stringify(formDataObject);
-
     try {
       const response = await fetch(`${apiUrl}/api/polls`, {
         headers: {
           "Content-Type": "application/json",
+          Authorization: `Bearer ${token}`,
         },
         method: "POST",
         body: data,
       });
 
       const result = await response.json();
-      if (result.poll_id) {
+      if (response.status === 422) {
+        const problems = result.fields
+          .map((field: { field: string; message: string }) => `${field.field} ${field.message}`)
+          .join("\n");
+        alert(problems);
+      } else if (result.poll_id) {
         alert(result.poll_id + " added successfully");
         addPoll(result);
         setOwnedPolls(polls);
@@ -102,19 +81,6 @@ export default function New() {
     <div className="h-full p-20">
       <h1 className="flex-row text-center text-2xl font-bold mb-4">NEW POLL TO BE CREATED</h1>
       <form className="text-xl flex-row max-w-sm mx-auto border rounded-xl  p-2" onSubmit={handleSubmit} ref={formRef}>
-        <div className="mb-5">
-          <label className="block mb-2  font-medium text-gray-900 dark:text-white">
-            POLL ID
-          </label>
-          <input
-            type="number"
-            id="poll_id"
-            name="poll_id"
-            className="bg-gray-50 border border-gray-300 text-gray-900  rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full p-2.5 "
-            placeholder="SAMPLE POLL"
-            required
-          />
-        </div>
         <div className="mb-5">
           <label className="block mb-2  font-medium text-gray-900">
             TITLE
@@ -174,14 +140,34 @@ export default function New() {
           </button>
         </div>
         <div className="mb-5">
-          <label className="block mb-2 font-medium text-gray-900 ">
-            STATUS:
+          <label htmlFor="voting_method" className="block mb-2 font-medium text-gray-900 ">
+            VOTING METHOD:
           </label>
-          <select name="status">
-            <option value="active">ACTIVE</option>
-            <option value="closed">CLOSED</option>
+          <select
+            id="voting_method"
+            name="voting_method"
+            defaultValue="single_choice"
+            className="bg-gray-50 border border-gray-300 text-gray-900 rounded-lg focus:ring-blue-500 focus:border-blue-500 block w-full p-2.5 "
+          >
+            <option value="single_choice">Single choice</option>
+            <option value="approval">Approval (pick several)</option>
+            <option value="ranked_choice">Ranked choice (instant runoff)</option>
+            <option value="schulze">Ranked choice (Condorcet / Schulze)</option>
+            <option value="score">Score (rate each option 0-5)</option>
           </select>
         </div>
+        <div className="mb-5">
+          <label className="font-medium text-gray-900 ">
+            <input type="checkbox" name="allow_vote_changes" defaultChecked className="mr-2" />
+            ALLOW VOTERS TO CHANGE THEIR VOTE
+          </label>
+        </div>
+        <div className="mb-5">
+          <label className="font-medium text-gray-900 ">
+            <input type="checkbox" name="draft" className="mr-2" />
+            SAVE AS DRAFT
+          </label>
+        </div>
         <button
           type="submit"
           className="text-white bg-blue-700 hover:bg-blue-800 focus:ring-4 focus:outline-none focus:ring-blue-300 font-medium rounded-lg text-sm w-full sm:w-auto px-5 py-2.5 text-center "
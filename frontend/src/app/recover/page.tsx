@@ -0,0 +1,112 @@
+"use client"
+
+import { useState } from "react";
+import { startRegistration } from '@simplewebauthn/browser';
+import Link from "next/link";
+
+export default function Recover() {
+    const apiUrl = process.env.NEXT_PUBLIC_API_URL || '';
+    const [name, setName] = useState('');
+    const [code, setCode] = useState('');
+    const [successMessage, setSuccessMessage] = useState('');
+    const [errorMessage, setErrorMessage] = useState('');
+
+    const handleRecover = async (event: React.FormEvent<HTMLFormElement>) => {
+        event.preventDefault();
+        setSuccessMessage('');
+        setErrorMessage('');
+
+        try {
+            // Step 1: Trade the recovery code for a token that can only enroll a passkey
+            const recoverResponse = await fetch(`${apiUrl}/api/auth/recover`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ user_name: name, code }),
+            });
+            const recoverJSON = await recoverResponse.json();
+            if (!recoverResponse.ok) {
+                setErrorMessage(recoverJSON.error);
+                return;
+            }
+            const headers = {
+                'Content-Type': 'application/json',
+                Authorization: `Bearer ${recoverJSON.recovery_token}`,
+            };
+
+            // Step 2: Enroll a new passkey with it
+            const response = await fetch(`${apiUrl}/api/auth/passkeys/start`, {
+                method: 'POST',
+                headers,
+                body: JSON.stringify({ nickname: 'Recovered passkey' }),
+            });
+            const jsonresp = await response.json();
+            const attResp = await startRegistration({ optionsJSON: jsonresp.publicKey });
+
+            const verificationResponse = await fetch(`${apiUrl}/api/auth/passkeys/finish?ceremony_id=` + jsonresp.ceremony_id, {
+                method: 'POST',
+                headers,
+                body: JSON.stringify(attResp),
+            });
+
+            if (verificationResponse.ok) {
+                setSuccessMessage('New passkey added, you can sign in with it now.');
+            } else {
+                setErrorMessage(`Recovery failed! Response: ${await verificationResponse.text()}`);
+            }
+        } catch (error) {
+            if (error instanceof Error) {
+                setErrorMessage(`Error:` + error.message);
+            }
+        }
+    };
+
+    return (
+        <div className="flex h-[100vh] items-center  justify-center bg-black">
+            <div className="w-full max-w-md p-8 bg-white shadow-lg rounded-lg">
+                <h1 className="text-2xl font-semibold text-gray-800 text-center mb-6">
+                    Recover account
+                </h1>
+                <form onSubmit={handleRecover} className="space-y-6">
+                    <div>
+                        <label htmlFor="name" className="block text-sm font-medium text-gray-700">
+                            User Name
+                        </label>
+                        <input
+                            id="name"
+                            name="name"
+                            type="text"
+                            required
+                            className="text-black mt-2 block w-full rounded-md shadow-sm focus:ring-indigo-500 focus:border-indigo-500"
+                            onChange={(e) => setName(e.target.value)}
+                        />
+                    </div>
+                    <div>
+                        <label htmlFor="code" className="block text-sm font-medium text-gray-700">
+                            Recovery code
+                        </label>
+                        <input
+                            id="code"
+                            name="code"
+                            type="text"
+                            required
+                            className="text-black mt-2 block w-full rounded-md shadow-sm focus:ring-indigo-500 focus:border-indigo-500"
+                            onChange={(e) => setCode(e.target.value)}
+                        />
+                    </div>
+                    <button
+                        type="submit"
+                        className="w-full bg-black text-white py-2 rounded-md font-medium shadow-md hover:bg-gray-800 focus:ring-2 focus:ring-indigo-400"
+                    >
+                        Add a new passkey
+                    </button>
+                    {successMessage && (
+                        <p className="mt-4 text-sm text-green-600">
+                            {successMessage} <Link href="/login" className="underline">Go to login</Link>
+                        </p>
+                    )}
+                    {errorMessage && <p className="mt-4 text-sm text-red-600">{errorMessage}</p>}
+                </form>
+            </div>
+        </div>
+    )
+}
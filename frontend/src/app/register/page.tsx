@@ -2,13 +2,14 @@
 
 import { useState } from "react";
 import { startRegistration } from '@simplewebauthn/browser';
-import { redirect } from "next/navigation";
+import Link from "next/link";
 
 export default function Register() {
     const apiUrl = process.env.NEXT_PUBLIC_API_URL || '';
     const [name, setName] = useState('');
     const [successMessage, setSuccessMessage] = useState('');
     const [errorMessage, setErrorMessage] = useState('');
+    const [recoveryCodes, setRecoveryCodes] = useState<string[]>([]);
 
     const handleRegister = async (event: React.FormEvent<HTMLFormElement>) => {
         // Reset messages
@@ -29,7 +30,7 @@ export default function Register() {
 
             // Step 3: POST the response to the server for verification
             
-            const verificationResponse = await fetch(`${apiUrl}/api/auth/finish_reg`, {
+            const verificationResponse = await fetch(`${apiUrl}/api/auth/finish_reg?ceremony_id=` + jsonresp.ceremony_id, {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
@@ -38,10 +39,11 @@ export default function Register() {
             });
 
             // Wait for the results of verification
-            const verificationJSON = await verificationResponse.text();
+            const verificationJSON = await verificationResponse.json();
 
             // Show UI based on the verification status
-            if (verificationJSON) {
+            if (verificationResponse.ok) {
+                setRecoveryCodes(verificationJSON.recovery_codes);
                 setSuccessMessage('Registration successful!');
             } else {
                 setErrorMessage(`Registration failed! Response: ${JSON.stringify(verificationJSON)}`);
@@ -91,7 +93,18 @@ export default function Register() {
                 >
                     Register
                 </button>
-                {successMessage && <p className="mt-4 text-sm text-green-600">{successMessage}</p> && redirect("/login")}
+                {successMessage && (
+                    <div className="mt-4 text-sm text-green-600">
+                        <p>{successMessage}</p>
+                        <p className="mt-2 text-gray-700">
+                            Save these recovery codes somewhere safe. Each one can be used once to add a new passkey if you lose yours, and they will not be shown again.
+                        </p>
+                        <ul className="mt-2 grid grid-cols-2 gap-1 font-mono text-black">
+                            {recoveryCodes.map((code) => <li key={code}>{code}</li>)}
+                        </ul>
+                        <Link href="/login" className="mt-4 block underline">Continue to login</Link>
+                    </div>
+                )}
                 {errorMessage && <p className="mt-4 text-sm text-red-600">{errorMessage}</p>}
             </form>
         </div>
@@ -14,7 +14,7 @@ interface PollOption {
     description: string;
     created_at: string;
     expiration_date?: string | null;
-    status: "Active" | "expired" | "closed";
+    status: "draft" | "scheduled" | "active" | "closed" | "expired" | "archived";
     options: PollOption[];
     users_voted: number[];
   }
@@ -14,16 +14,19 @@ interface Poll {
   description: string;
   created_at: string; // ISO 8601 string for DateTime<Utc>
   expiration_date?: string | null; // Optional ISO 8601 string for Option<DateTime<Utc>>
-  status: "Active" | "expired" | "closed"; // Enum-like string for the status field
+  status: "draft" | "scheduled" | "active" | "closed" | "expired" | "archived"; // Enum-like string for the status field
   options: PollOption[]; // Array of PollOption
 }
 
 export interface UserStore {
   name: string;
+  userId: string;
+  token: string;
+  refreshToken: string;
   ownedPolls: Poll[];
   setOwnedPolls: (polls: Poll[]) => void;
   clearOwnedPolls: (name: string) => void;
-  setUser: (name: string) => void;
+  setUser: (name: string, userId: string, token: string, refreshToken: string) => void;
   ClearUser: (name: string) => void;
 }
 
@@ -31,15 +34,19 @@ const useUserStore = create<UserStore>()(
   persist(
     (set) => ({
       name: "",
+      userId: "",
+      token: "",
+      refreshToken: "",
       ownedPolls: [],
       setOwnedPolls: (polls) => set((state) => ({
-        ownedPolls: polls.filter((poll) => poll.creator == state.name),
+        ownedPolls: polls.filter((poll) => poll.creator == state.userId),
       })),
       clearOwnedPolls: () => set(() => ({
         ownedPolls: []
       })),
-      setUser: (name: string) => set(() => ({ name })),
-      ClearUser: () => set(({name: "", ownedPolls: []})),
+      setUser: (name: string, userId: string, token: string, refreshToken: string) =>
+        set(() => ({ name, userId, token, refreshToken })),
+      ClearUser: () => set(({name: "", userId: "", token: "", refreshToken: "", ownedPolls: []})),
     }),
     {
       name: "user-storage", 
@@ -0,0 +1,8 @@
+//! Secret sharing primitives.
+//!
+//! [`sss`] holds the Shamir split/reconstruct used by the backend for social recovery; the
+//! binary in `main.rs` is a standalone demo of verifiable sharing over small integers.
+
+pub mod sss;
+
+pub use sss::{reconstruct, split, Share, SharingError};
@@ -1,124 +1,237 @@
-use core::ops::{Add, Mul};
+//! Shamir secret sharing over the prime field GF(2^521 - 1).
+//!
+//! A secret of up to [`MAX_SECRET_LEN`] bytes becomes the constant term of a random polynomial
+//! of degree `threshold - 1`; each share is a point on it. Any `threshold` shares give the
+//! polynomial back through Lagrange interpolation at zero, fewer reveal nothing about it.
 
+use core::fmt;
+use core::str::FromStr;
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
 use rand::Rng;
 
-pub fn gcd(a: i32, b: i32) -> i32 {
-    if b == 0 {
-        a
-    } else {
-        gcd(b, a % b)
-    }
+/// Longest secret that fits in the field once the length marker byte is added.
+pub const MAX_SECRET_LEN: usize = 64;
+
+/// The Mersenne prime 2^521 - 1.
+fn prime() -> BigUint {
+    (BigUint::one() << 521u32) - BigUint::one()
 }
 
-fn calc_y(x: i32, poly: &Vec<i32>) -> i32 {
-    let mut temp = 1;
-    let mut y = 0;
+/// One point `(x, y)` on the sharing polynomial. `x` is never zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub x: u32,
+    pub y: BigUint,
+}
 
-    for coeff in poly {
-        y = y + (coeff * temp);
-        temp *= x
+/// Shares are written as `<x>:<y in hex>` so they can be stored as plain strings.
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{:x}", self.x, self.y)
     }
-
-    return y;
 }
 
-fn encode(s: i32, n: i32, k: i32, points: &mut Vec<(i32, i32)>) {
-    let mut poly: Vec<i32> = Vec::with_capacity((k - 1) as usize);
-    poly.push(s);
-    let mut rng = rand::thread_rng();
+impl FromStr for Share {
+    type Err = SharingError;
 
-    for _ in 1..k {
-        let mut p = 0;
-        while p == 0 {
-            p = rng.gen_range(1..997)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s.split_once(':').ok_or(SharingError::MalformedShare)?;
+        let x = x.parse().map_err(|_| SharingError::MalformedShare)?;
+        let y = BigUint::parse_bytes(y.as_bytes(), 16).ok_or(SharingError::MalformedShare)?;
+        if x == 0 || y >= prime() {
+            return Err(SharingError::MalformedShare);
         }
-        poly.push(p)
+        Ok(Share { x, y })
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharingError {
+    /// The threshold must be at least 1 and at most the number of shares
+    InvalidThreshold,
+    /// The secret is longer than [`MAX_SECRET_LEN`]
+    SecretTooLong,
+    /// No shares were given to reconstruct from
+    NotEnoughShares,
+    /// Two shares have the same `x`
+    DuplicateShare,
+    /// A share string could not be parsed
+    MalformedShare,
+    /// The shares do not lie on one polynomial, or there were fewer than the threshold
+    InconsistentShares,
+}
 
-    for x in 1..n + 1 {
-        let y: i32 = calc_y(x, &poly);
-        points.push((x, y));
+impl fmt::Display for SharingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            SharingError::InvalidThreshold => "threshold must be between 1 and the share count",
+            SharingError::SecretTooLong => "secret is too long to share",
+            SharingError::NotEnoughShares => "no shares to reconstruct from",
+            SharingError::DuplicateShare => "the same share was given twice",
+            SharingError::MalformedShare => "malformed share",
+            SharingError::InconsistentShares => "shares do not reconstruct a secret",
+        };
+        f.write_str(message)
     }
 }
 
-struct Fraction {
-    num: i32,
-    den: i32,
+impl std::error::Error for SharingError {}
+
+fn random_element(rng: &mut impl Rng, p: &BigUint) -> BigUint {
+    let mut bytes = [0u8; 66];
+    rng.fill(&mut bytes[..]);
+    BigUint::from_bytes_be(&bytes) % p
 }
 
-impl Fraction {
-    fn new(num: i32, den: i32) -> Self {
-        Self { num, den }
-    }
+/// Evaluates the polynomial with coefficients `poly` (constant term first) at `x`.
+fn calc_y(x: &BigUint, poly: &[BigUint], p: &BigUint) -> BigUint {
+    poly.iter()
+        .rev()
+        .fold(BigUint::zero(), |acc, coeff| (acc * x + coeff) % p)
 }
 
-impl Add for Fraction {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        let mut num_1 = (self.num * other.den) + (other.num * self.den);
-        let mut den_1 = self.den * other.den;
-        let _gcd = gcd(num_1, den_1);
-        num_1 /= _gcd;
-        den_1 /= _gcd;
-        Self {
-            num: num_1,
-            den: den_1,
-        }
+/// Splits `secret` into `shares` shares, any `threshold` of which can rebuild it.
+pub fn split(secret: &[u8], threshold: usize, shares: usize) -> Result<Vec<Share>, SharingError> {
+    if threshold == 0 || threshold > shares || shares > u32::MAX as usize {
+        return Err(SharingError::InvalidThreshold);
+    }
+    if secret.len() > MAX_SECRET_LEN {
+        return Err(SharingError::SecretTooLong);
+    }
+
+    let p = prime();
+    let mut rng = rand::thread_rng();
+
+    // The leading 1 keeps leading zero bytes of the secret through the round trip
+    let mut marked = Vec::with_capacity(secret.len() + 1);
+    marked.push(1u8);
+    marked.extend_from_slice(secret);
+
+    let mut poly = Vec::with_capacity(threshold);
+    poly.push(BigUint::from_bytes_be(&marked));
+    for _ in 1..threshold {
+        poly.push(random_element(&mut rng, &p));
     }
+
+    Ok((1..=shares as u32)
+        .map(|x| Share {
+            x,
+            y: calc_y(&BigUint::from(x), &poly, &p),
+        })
+        .collect())
 }
 
-impl Mul for Fraction {
-    type Output = Self;
-
-    fn mul(self, other: Self) -> Self {
-        let mut num_1 = self.num * other.num;
-        let mut den_1 = self.den * other.den;
-        let _gcd = gcd(num_1, den_1);
-        num_1 /= _gcd;
-        den_1 /= _gcd;
-        Self {
-            num: num_1,
-            den: den_1,
+/// Rebuilds the secret from at least `threshold` distinct shares of it.
+pub fn reconstruct(shares: &[Share]) -> Result<Vec<u8>, SharingError> {
+    if shares.is_empty() {
+        return Err(SharingError::NotEnoughShares);
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|other| other.x == share.x) {
+            return Err(SharingError::DuplicateShare);
         }
     }
-}
 
-fn decode(k: i32, x: Vec<i32>, y: Vec<i32>) -> i32 {
-    //get the polynomial back from x,y using langranges no need to iterate x fully rather iterate x,y till k
-    let mut ans = Fraction::new(0, 1);
-    for i in 0..k {
-        let mut l = Fraction::new(y[i as usize], 1);
-        for j in 0..k {
-            if i != j {
-                //let temp = Fraction::new(-x[j as usize], x[i as usize] - x[j as usize]);
-                let num0 = -x[j as usize];
-                let den0 = x[i as usize] - x[j as usize];
+    let p = prime();
+    let exponent = &p - BigUint::from(2u32);
 
-                let temp = Fraction::new(num0, den0);
-                l = l * temp;
+    // Lagrange interpolation at x = 0
+    let mut secret = BigUint::zero();
+    for (i, share_i) in shares.iter().enumerate() {
+        let x_i = BigUint::from(share_i.x);
+        let mut num = BigUint::one();
+        let mut den = BigUint::one();
+        for (j, share_j) in shares.iter().enumerate() {
+            if i != j {
+                let x_j = BigUint::from(share_j.x);
+                num = num * &x_j % &p;
+                den = den * ((&x_j + &p - &x_i) % &p) % &p;
             }
         }
-        ans = ans + l;
+        // Division is multiplication by the inverse, a^(p-2) by Fermat's little theorem
+        let l = num * den.modpow(&exponent, &p) % &p;
+        secret = (secret + &share_i.y * l) % &p;
+    }
+
+    match secret.to_bytes_be().split_first() {
+        Some((1, rest)) if rest.len() <= MAX_SECRET_LEN => Ok(rest.to_vec()),
+        _ => Err(SharingError::InconsistentShares),
     }
-    return ans.num;
 }
-fn main() {
-    let s = 65;
-    let n = 4;
-    let k = 2;
 
-    let mut points: Vec<(i32, i32)> = Vec::with_capacity(n as usize);
-    let mut x = vec![0; k];
-    let mut y = vec![0; k];
-    encode(s, n, k as i32, &mut points);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_threshold_of_shares_rebuilds_the_secret() {
+        let secret = b"correct horse battery staple";
+        for (threshold, count) in [(1, 1), (1, 3), (2, 3), (3, 5), (5, 5)] {
+            let shares = split(secret, threshold, count).unwrap();
+            assert_eq!(shares.len(), count);
+            for start in 0..=count - threshold {
+                let subset = &shares[start..start + threshold];
+                assert_eq!(
+                    reconstruct(subset).unwrap(),
+                    secret,
+                    "{threshold} of {count}"
+                );
+            }
+            assert_eq!(reconstruct(&shares).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn keeps_leading_zero_bytes_and_empty_secrets() {
+        for secret in [&[0u8, 0, 7, 0][..], &[0u8; MAX_SECRET_LEN][..], &[][..]] {
+            let shares = split(secret, 2, 3).unwrap();
+            assert_eq!(reconstruct(&shares[1..]).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn fewer_than_threshold_shares_do_not_give_the_secret() {
+        let secret = b"attack at dawn";
+        let shares = split(secret, 3, 5).unwrap();
+        for subset in [&shares[..2], &shares[3..], &shares[..1]] {
+            assert_ne!(reconstruct(subset).ok().as_deref(), Some(&secret[..]));
+        }
+    }
+
+    #[test]
+    fn rejects_the_same_share_twice() {
+        let shares = split(b"secret", 2, 3).unwrap();
+        let repeated = [shares[0].clone(), shares[0].clone()];
+        assert_eq!(reconstruct(&repeated), Err(SharingError::DuplicateShare));
+        assert_eq!(reconstruct(&[]), Err(SharingError::NotEnoughShares));
+    }
+
+    #[test]
+    fn rejects_thresholds_outside_the_share_count() {
+        assert_eq!(split(b"secret", 0, 3), Err(SharingError::InvalidThreshold));
+        assert_eq!(split(b"secret", 4, 3), Err(SharingError::InvalidThreshold));
+    }
 
-    for i in 0..k {
-        let (x1, y1) = points[i];
-        x[i as usize] = x1;
-        y[i as usize] = y1;
+    #[test]
+    fn rejects_secrets_longer_than_the_field() {
+        let secret = [1u8; MAX_SECRET_LEN + 1];
+        assert_eq!(split(&secret, 2, 3), Err(SharingError::SecretTooLong));
     }
 
-    let ans = decode(k as i32, x, y);
-    println!("the secret is {} ", ans);
+    #[test]
+    fn shares_round_trip_through_their_string_form() {
+        for share in split(&[0xff; MAX_SECRET_LEN], 3, 4).unwrap() {
+            let parsed: Share = share.to_string().parse().unwrap();
+            assert_eq!(parsed, share);
+        }
+        for malformed in ["", "1", "0:ff", "x:ff", "1:zz", "1:"] {
+            assert_eq!(
+                malformed.parse::<Share>(),
+                Err(SharingError::MalformedShare),
+                "{malformed:?}"
+            );
+        }
+    }
 }